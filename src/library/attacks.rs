@@ -0,0 +1,290 @@
+use crate::library::bitboard::{self, Bitboard};
+use crate::library::game::{Color, Field, Move, PieceInstance, PieceType, State};
+use std::cmp::max;
+
+/*
+    Queries about which pieces attack which fields, e.g. for hints or evaluation. A field is attacked by a piece
+    if the piece could capture on it (ignoring pins), so pawns attack diagonally and a piece also attacks the fields
+    of its own pieces it defends.
+
+    A piece is pinned if it stands between its king and an enemy rook, bishop or queen on the same line, i.e. it
+    may only move along the ray between the two (onto the fields between them or by capturing the pinning piece).
+
+    The static exchange evaluation (SEE) of a move is the material it wins (in centipawns) if both players keep
+    recapturing on its target field with their least valuable piece for as long as that pays off. Pieces behind a
+    capturing rook, bishop or queen join in once it has left its field (x-rays); pins are ignored.
+*/
+#[derive(PartialEq, Clone, Debug)]
+pub struct Pin {
+    pinned: PieceInstance,
+    pinner: PieceInstance,
+    ray: Vec<Field>,
+}
+
+impl Pin {
+    pub fn pinned(&self) -> &PieceInstance {
+        &self.pinned
+    }
+
+    pub fn pinner(&self) -> &PieceInstance {
+        &self.pinner
+    }
+
+    // the fields from the king (excluded) to the pinning piece (included)
+    pub fn ray(&self) -> &Vec<Field> {
+        &self.ray
+    }
+}
+
+impl State {
+    // the pieces of the given color attacking the field
+    pub fn attackers(&self, field: &Field, color: &Color) -> Vec<PieceInstance> {
+        self.piece_instances(self.board().attackers(field, color))
+    }
+
+    // the pieces giving check to the player to move
+    pub fn checkers(&self) -> Vec<PieceInstance> {
+        match self.board().king_field(self.turn()) {
+            Some(king_field) => self.attackers(&king_field, self.turn_rev()),
+            None => Vec::new(),
+        }
+    }
+
+    // the pieces of the given color that are pinned to their king
+    pub fn pins(&self, color: &Color) -> Vec<Pin> {
+        let board = self.board();
+        let king_field = match board.king_field(color) {
+            Some(field) => field,
+            None => return Vec::new(),
+        };
+        let enemy_color = match color {
+            Color::White => Color::Black,
+            _ => Color::White,
+        };
+
+        // the enemy pieces that would attack the king if nothing stood in between
+        let enemy = |piecetype: PieceType| board.pieces(&enemy_color, &piecetype);
+        let sliders = (bitboard::rook_attacks(&king_field, bitboard::EMPTY)
+            & (enemy(PieceType::Rook) | enemy(PieceType::Queen)))
+            | (bitboard::bishop_attacks(&king_field, bitboard::EMPTY)
+                & (enemy(PieceType::Bishop) | enemy(PieceType::Queen)));
+
+        let mut pins = Vec::new();
+        for pinner_field in bitboard::fields(sliders) {
+            let between = bitboard::between(&king_field, &pinner_field);
+            let blockers = between & board.occupied();
+            if blockers.count_ones() != 1
+                || blockers & board.pieces_of_color(color) == bitboard::EMPTY
+            {
+                continue;
+            }
+            let mut pieces = self.piece_instances(blockers | bitboard::bit(&pinner_field));
+            let (pinned, pinner) = match pieces[0].piece().color() == color {
+                true => (pieces.remove(0), pieces.remove(0)),
+                false => (pieces.remove(1), pieces.remove(0)),
+            };
+            pins.push(Pin {
+                pinned,
+                pinner,
+                ray: bitboard::fields(between | bitboard::bit(&pinner_field)).collect(),
+            });
+        }
+        pins
+    }
+
+    // the fields attacked by at least one piece of the given color
+    pub fn controlled_fields(&self, color: &Color) -> Vec<Field> {
+        let board = self.board();
+        let controlled = bitboard::fields(board.pieces_of_color(color))
+            .map(|field| board.attacks(&board.get_piece_on_field(&field), &field))
+            .fold(bitboard::EMPTY, |controlled, attacks| controlled | attacks);
+        bitboard::fields(controlled).collect()
+    }
+
+    pub fn see(&self, chess_move: &Move) -> i32 {
+        let board = self.board();
+        let target = chess_move.target_field();
+        let mut occupied = board.occupied() & !bitboard::bit(chess_move.start_field());
+
+        // an en passant capture takes a pawn off another field
+        let captured = match chess_move.captured() {
+            Some(piece) => *piece.piecetype(),
+            None => PieceType::None,
+        };
+        if let Some(captured_field) = chess_move.captured_field() {
+            occupied &= !bitboard::bit(&captured_field);
+        }
+        let mut gains = vec![captured.value()];
+        let mut on_target = match chess_move.promotion() {
+            Some(promotion) => {
+                gains[0] += promotion.value() - PieceType::Pawn.value();
+                *promotion
+            }
+            None => *chess_move.piece().piecetype(),
+        };
+
+        // the players take turns recapturing with their least valuable attacker
+        let mut color = match chess_move.piece().color() {
+            Color::White => Color::Black,
+            _ => Color::White,
+        };
+        loop {
+            let attackers = board.attackers_with_occupancy(target, &color, occupied);
+            let attacker = bitboard::fields(attackers).min_by_key(|field| {
+                match board.get_type_of_piece_on_field(field) {
+                    PieceType::King => i32::MAX,
+                    piecetype => piecetype.value(),
+                }
+            });
+            let attacker_field = match attacker {
+                Some(field) => field,
+                None => break,
+            };
+            let attacker_type = board.get_type_of_piece_on_field(&attacker_field);
+            occupied &= !bitboard::bit(&attacker_field);
+
+            // the king may only capture if the field isn't defended anymore
+            let opponent = match color {
+                Color::White => Color::Black,
+                _ => Color::White,
+            };
+            if attacker_type == PieceType::King
+                && board.attackers_with_occupancy(target, &opponent, occupied) != bitboard::EMPTY
+            {
+                break;
+            }
+
+            gains.push(on_target.value() - gains[gains.len() - 1]);
+            on_target = attacker_type;
+            color = opponent;
+        }
+
+        // either player may stop recapturing, so each capture is only made if it gains material
+        for index in (1..gains.len()).rev() {
+            gains[index - 1] = -max(-gains[index - 1], gains[index]);
+        }
+        gains[0]
+    }
+
+    fn piece_instances(&self, pieces: Bitboard) -> Vec<PieceInstance> {
+        bitboard::fields(pieces)
+            .map(|field| PieceInstance::new(self.board().get_piece_on_field(&field), field))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::library::game::Piece;
+
+    fn fields(pieces: &[PieceInstance]) -> Vec<Field> {
+        pieces.iter().map(|piece| piece.field().clone()).collect()
+    }
+
+    #[test]
+    fn attackers() {
+        let state = State::load_game_from_fen(String::from(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        ));
+        // e5 is attacked by the knight on f3 and defended by the knight on c6
+        assert_eq!(
+            fields(&state.attackers(&Field(4, 4), &Color::White)),
+            vec![Field(2, 5)]
+        );
+        assert_eq!(
+            fields(&state.attackers(&Field(4, 4), &Color::Black)),
+            vec![Field(5, 2)]
+        );
+        // f7 is attacked by the bishop and defended by the king
+        let attackers = state.attackers(&Field(6, 5), &Color::White);
+        assert_eq!(attackers.len(), 1);
+        assert_eq!(
+            attackers[0].piece(),
+            &Piece::new(Color::White, PieceType::Bishop)
+        );
+        assert_eq!(
+            fields(&state.attackers(&Field(6, 5), &Color::Black)),
+            vec![Field(7, 4)]
+        );
+        assert!(state.attackers(&Field(3, 0), &Color::White).is_empty());
+    }
+
+    #[test]
+    fn checkers() {
+        let state = State::new(None);
+        assert!(state.checkers().is_empty());
+
+        // a double check by the knight and the rook
+        let state = State::load_game_from_fen(String::from("4k3/8/3N4/8/8/8/8/4R1K1 b - - 0 1"));
+        assert_eq!(fields(&state.checkers()), vec![Field(0, 4), Field(5, 3)]);
+    }
+
+    #[test]
+    fn pins() {
+        let state =
+            State::load_game_from_fen(String::from("4k3/8/4r3/8/1b2N3/8/3P4/4K2r w - - 0 1"));
+        let pins = state.pins(&Color::White);
+        assert_eq!(pins.len(), 2);
+        assert_eq!(pins[0].pinned().field(), &Field(1, 3));
+        assert_eq!(pins[0].pinner().field(), &Field(3, 1));
+        assert_eq!(pins[0].ray(), &vec![Field(1, 3), Field(2, 2), Field(3, 1)]);
+        assert_eq!(pins[1].pinned().field(), &Field(3, 4));
+        assert_eq!(
+            pins[1].pinner().piece(),
+            &Piece::new(Color::Black, PieceType::Rook)
+        );
+        assert_eq!(pins[1].ray().len(), 5);
+
+        // two pieces in between are not pinned
+        let state = State::load_game_from_fen(String::from("4k3/8/4r3/4P3/4N3/8/8/R3K3 w - - 0 1"));
+        assert!(state.pins(&Color::White).is_empty());
+        assert!(state.pins(&Color::Black).is_empty());
+    }
+
+    #[test]
+    fn see() {
+        let see = |fen: &str, san: &str| {
+            let state = State::load_game_from_fen(String::from(fen));
+            state.see(&state.parse_san(san).unwrap())
+        };
+
+        // an undefended pawn, a defended one and a defended pawn taken by the queen
+        assert_eq!(
+            see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "Rxe5"),
+            100
+        );
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/8/4RK2 w - - 0 1", "Rxe5"), -400);
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", "Qxe5"), -800);
+
+        // the queen behind the rook joins in (x-ray), so the knight can be taken
+        assert_eq!(see("4k3/8/4r3/4n3/8/8/4R3/4QK2 w - - 0 1", "Rxe5"), 300);
+        assert_eq!(see("4k3/8/4r3/4n3/8/8/4R3/5K2 w - - 0 1", "Rxe5"), -200);
+
+        // the king can only recapture if the field isn't defended anymore
+        assert_eq!(see("8/8/8/8/8/2k5/3r4/3RK3 w - - 0 1", "Rxd2"), 500);
+        assert_eq!(see("3r4/8/8/8/8/2k5/3r4/3RK3 w - - 0 1", "Rxd2"), 0);
+        assert_eq!(see("8/8/8/8/8/2k5/3p4/3R2K1 w - - 0 1", "Rxd2"), -400);
+
+        // en passant, promotion and quiet moves
+        assert_eq!(see("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1", "dxe6"), 100);
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a8=Q"), -100);
+        assert_eq!(see("4k3/8/8/8/p7/8/8/2N1K3 w - - 0 1", "Ne2"), 0);
+        assert_eq!(see("4k3/8/8/8/p7/8/8/2N1K3 w - - 0 1", "Nb3"), -300);
+    }
+
+    #[test]
+    fn controlled_fields() {
+        let state = State::new(None);
+        let white = state.controlled_fields(&Color::White);
+        assert_eq!(white.len(), 22);
+        assert!(white.contains(&Field(2, 0)));
+        assert!(white.contains(&Field(0, 3)));
+        assert!(!white.contains(&Field(3, 4)));
+
+        let black = state.controlled_fields(&Color::Black);
+        assert_eq!(black.len(), 22);
+        assert!(black.contains(&Field(5, 7)));
+    }
+}
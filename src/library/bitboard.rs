@@ -0,0 +1,513 @@
+use crate::library::game::{Color, Field, Piece, PieceType, PositionMatrix};
+
+/*
+    A bitboard is a set of fields, where bit 'rank * 8 + file' represents the field 'Field(rank, file)'.
+    i.e., bit 0 is a1, bit 7 is h1 and bit 63 is h8.
+
+    The board keeps one bitboard per piece type and one per color. The piece on a field is the combination
+    of the piece type and color bitboards containing it.
+*/
+pub type Bitboard = u64;
+
+pub const EMPTY: Bitboard = 0;
+pub const DARK_FIELDS: Bitboard = 0xAA55_AA55_AA55_AA55;
+pub const BACK_RANKS: Bitboard = 0xFF00_0000_0000_00FF;
+
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+const FILE_B: Bitboard = FILE_A << 1;
+const FILE_G: Bitboard = FILE_A << 6;
+const FILE_H: Bitboard = FILE_A << 7;
+
+const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+// the directions of the rays as (rank, file) steps
+// the first four increase the field index, the last four decrease it
+const DIRECTIONS: [(isize, isize); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (1, -1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (-1, 1),
+];
+const STRAIGHT_DIRECTIONS: [usize; 4] = [0, 2, 4, 6];
+const DIAGONAL_DIRECTIONS: [usize; 4] = [1, 3, 5, 7];
+
+static KNIGHT_ATTACKS: [Bitboard; 64] = knight_attacks_table();
+static KING_ATTACKS: [Bitboard; 64] = king_attacks_table();
+static PAWN_ATTACKS: [[Bitboard; 64]; 2] = pawn_attacks_table();
+static RAYS: [[Bitboard; 64]; 8] = rays_table();
+
+pub fn square(field: &Field) -> usize {
+    field.0 * 8 + field.1
+}
+
+pub fn field(square: usize) -> Field {
+    Field(square / 8, square % 8)
+}
+
+pub fn bit(field: &Field) -> Bitboard {
+    1 << square(field)
+}
+
+// iterates over the fields of a bitboard, starting at a1
+pub struct Fields(Bitboard);
+
+impl Iterator for Fields {
+    type Item = Field;
+
+    fn next(&mut self) -> Option<Field> {
+        if self.0 == EMPTY {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(field(square))
+    }
+}
+
+pub fn fields(bitboard: Bitboard) -> Fields {
+    Fields(bitboard)
+}
+
+const fn knight_attacks_table() -> [Bitboard; 64] {
+    let mut table = [EMPTY; 64];
+    let mut square = 0;
+    while square < 64 {
+        let b: Bitboard = 1 << square;
+        table[square] = ((b << 17) & !FILE_A)
+            | ((b << 15) & !FILE_H)
+            | ((b << 10) & !(FILE_A | FILE_B))
+            | ((b << 6) & !(FILE_G | FILE_H))
+            | ((b >> 17) & !FILE_H)
+            | ((b >> 15) & !FILE_A)
+            | ((b >> 10) & !(FILE_G | FILE_H))
+            | ((b >> 6) & !(FILE_A | FILE_B));
+        square += 1;
+    }
+    table
+}
+
+const fn king_attacks_table() -> [Bitboard; 64] {
+    let mut table = [EMPTY; 64];
+    let mut square = 0;
+    while square < 64 {
+        let b: Bitboard = 1 << square;
+        table[square] = (b << 8)
+            | (b >> 8)
+            | (((b << 1) | (b << 9) | (b >> 7)) & !FILE_A)
+            | (((b >> 1) | (b >> 9) | (b << 7)) & !FILE_H);
+        square += 1;
+    }
+    table
+}
+
+const fn pawn_attacks_table() -> [[Bitboard; 64]; 2] {
+    let mut table = [[EMPTY; 64]; 2];
+    let mut square = 0;
+    while square < 64 {
+        let b: Bitboard = 1 << square;
+        table[0][square] = ((b << 9) & !FILE_A) | ((b << 7) & !FILE_H);
+        table[1][square] = ((b >> 7) & !FILE_A) | ((b >> 9) & !FILE_H);
+        square += 1;
+    }
+    table
+}
+
+const fn rays_table() -> [[Bitboard; 64]; 8] {
+    let mut table = [[EMPTY; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let mut square = 0;
+        while square < 64 {
+            let mut rank = (square / 8) as isize + DIRECTIONS[direction].0;
+            let mut file = (square % 8) as isize + DIRECTIONS[direction].1;
+            while rank >= 0 && rank < 8 && file >= 0 && file < 8 {
+                table[direction][square] |= 1 << (rank * 8 + file);
+                rank += DIRECTIONS[direction].0;
+                file += DIRECTIONS[direction].1;
+            }
+            square += 1;
+        }
+        direction += 1;
+    }
+    table
+}
+
+pub fn knight_attacks(field: &Field) -> Bitboard {
+    KNIGHT_ATTACKS[square(field)]
+}
+
+pub fn king_attacks(field: &Field) -> Bitboard {
+    KING_ATTACKS[square(field)]
+}
+
+// the fields a pawn of the given color attacks (diagonally forward)
+pub fn pawn_attacks(color: &Color, field: &Field) -> Bitboard {
+    PAWN_ATTACKS[color_index(color)][square(field)]
+}
+
+// the fields along a ray up to and including the first occupied field
+fn ray_attacks(direction: usize, square: usize, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[direction][square];
+    let blockers = ray & occupied;
+    if blockers == EMPTY {
+        return ray;
+    }
+    let blocker = match direction {
+        0..=3 => blockers.trailing_zeros() as usize,
+        _ => 63 - blockers.leading_zeros() as usize,
+    };
+    ray ^ RAYS[direction][blocker]
+}
+
+pub fn rook_attacks(field: &Field, occupied: Bitboard) -> Bitboard {
+    STRAIGHT_DIRECTIONS
+        .iter()
+        .fold(EMPTY, |attacks, direction| {
+            attacks | ray_attacks(*direction, square(field), occupied)
+        })
+}
+
+pub fn bishop_attacks(field: &Field, occupied: Bitboard) -> Bitboard {
+    DIAGONAL_DIRECTIONS
+        .iter()
+        .fold(EMPTY, |attacks, direction| {
+            attacks | ray_attacks(*direction, square(field), occupied)
+        })
+}
+
+pub fn queen_attacks(field: &Field, occupied: Bitboard) -> Bitboard {
+    rook_attacks(field, occupied) | bishop_attacks(field, occupied)
+}
+
+// the fields strictly between two fields on the same rank, file or diagonal
+pub fn between(from: &Field, to: &Field) -> Bitboard {
+    for ray in RAYS.iter() {
+        if ray[square(from)] & bit(to) != EMPTY {
+            return ray[square(from)] & !(ray[square(to)] | bit(to));
+        }
+    }
+    EMPTY
+}
+
+fn piece_index(piecetype: &PieceType) -> usize {
+    match piecetype {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+        PieceType::None => panic!("Empty fields have no bitboard!"),
+    }
+}
+
+fn color_index(color: &Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+        Color::None => panic!("Empty fields have no bitboard!"),
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Board {
+    pieces: [Bitboard; 6],
+    colors: [Bitboard; 2],
+}
+
+impl Board {
+    pub fn empty() -> Board {
+        Board {
+            pieces: [EMPTY; 6],
+            colors: [EMPTY; 2],
+        }
+    }
+
+    pub fn from_matrix(matrix: &PositionMatrix) -> Board {
+        let mut board = Board::empty();
+        for (i, rank) in matrix.0.iter().enumerate() {
+            for (j, piece) in rank.iter().enumerate() {
+                if piece.piecetype() != &PieceType::None {
+                    board.place_piece(*piece, &Field(i, j));
+                }
+            }
+        }
+        board
+    }
+
+    // the matrix view of the board, indexed by [rank][file]
+    pub fn to_matrix(&self) -> PositionMatrix {
+        let matrix = (0..8)
+            .map(|rank| {
+                (0..8)
+                    .map(|file| self.get_piece_on_field(&Field(rank, file)))
+                    .collect()
+            })
+            .collect();
+        PositionMatrix(matrix)
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
+    }
+
+    pub fn pieces_of_color(&self, color: &Color) -> Bitboard {
+        self.colors[color_index(color)]
+    }
+
+    pub fn pieces_of_type(&self, piecetype: &PieceType) -> Bitboard {
+        self.pieces[piece_index(piecetype)]
+    }
+
+    pub fn pieces(&self, color: &Color, piecetype: &PieceType) -> Bitboard {
+        self.pieces_of_color(color) & self.pieces_of_type(piecetype)
+    }
+
+    pub fn has_piece_on_field(&self, field: &Field) -> bool {
+        self.occupied() & bit(field) != EMPTY
+    }
+
+    pub fn get_piece_on_field(&self, field: &Field) -> Piece {
+        Piece::new(
+            self.get_color_of_piece_on_field(field),
+            self.get_type_of_piece_on_field(field),
+        )
+    }
+
+    pub fn get_color_of_piece_on_field(&self, field: &Field) -> Color {
+        if self.colors[0] & bit(field) != EMPTY {
+            Color::White
+        } else if self.colors[1] & bit(field) != EMPTY {
+            Color::Black
+        } else {
+            Color::None
+        }
+    }
+
+    pub fn get_type_of_piece_on_field(&self, field: &Field) -> PieceType {
+        for (index, piecetype) in PIECE_TYPES.iter().enumerate() {
+            if self.pieces[index] & bit(field) != EMPTY {
+                return *piecetype;
+            }
+        }
+        PieceType::None
+    }
+
+    pub fn remove_piece_from_field(&mut self, field: &Field) -> Piece {
+        let piece = self.get_piece_on_field(field);
+        if piece.piecetype() != &PieceType::None {
+            self.pieces[piece_index(piece.piecetype())] &= !bit(field);
+            self.colors[color_index(piece.color())] &= !bit(field);
+        }
+        piece
+    }
+
+    pub fn place_piece(&mut self, piece: Piece, field: &Field) -> Piece {
+        let current_piece = self.remove_piece_from_field(field);
+        if piece.piecetype() != &PieceType::None {
+            self.pieces[piece_index(piece.piecetype())] |= bit(field);
+            self.colors[color_index(piece.color())] |= bit(field);
+        }
+        current_piece
+    }
+
+    pub fn king_field(&self, color: &Color) -> Option<Field> {
+        fields(self.pieces(color, &PieceType::King)).next()
+    }
+
+    // the fields a piece on the given field attacks with the current occupancy
+    pub fn attacks(&self, piece: &Piece, field: &Field) -> Bitboard {
+        match piece.piecetype() {
+            PieceType::Pawn => pawn_attacks(piece.color(), field),
+            PieceType::Knight => knight_attacks(field),
+            PieceType::Bishop => bishop_attacks(field, self.occupied()),
+            PieceType::Rook => rook_attacks(field, self.occupied()),
+            PieceType::Queen => queen_attacks(field, self.occupied()),
+            PieceType::King => king_attacks(field),
+            PieceType::None => EMPTY,
+        }
+    }
+
+    // all pieces of the given color that attack the field
+    pub fn attackers(&self, field: &Field, color: &Color) -> Bitboard {
+        self.attackers_with_occupancy(field, color, self.occupied())
+    }
+
+    // like 'attackers', but only the pieces on the given occupied fields count (as attackers and as
+    // blockers), so taking pieces away reveals the ones behind them
+    pub fn attackers_with_occupancy(
+        &self,
+        field: &Field,
+        color: &Color,
+        occupied: Bitboard,
+    ) -> Bitboard {
+        let opposite_color = match color {
+            Color::White => Color::Black,
+            _ => Color::White,
+        };
+        let straight =
+            self.pieces_of_type(&PieceType::Rook) | self.pieces_of_type(&PieceType::Queen);
+        let diagonal =
+            self.pieces_of_type(&PieceType::Bishop) | self.pieces_of_type(&PieceType::Queen);
+
+        // a piece attacks a field, if the same piece on the field would attack the piece
+        let attackers = (pawn_attacks(&opposite_color, field)
+            & self.pieces_of_type(&PieceType::Pawn))
+            | (knight_attacks(field) & self.pieces_of_type(&PieceType::Knight))
+            | (king_attacks(field) & self.pieces_of_type(&PieceType::King))
+            | (rook_attacks(field, occupied) & straight)
+            | (bishop_attacks(field, occupied) & diagonal);
+
+        attackers & self.pieces_of_color(color) & occupied
+    }
+
+    pub fn is_field_attacked(&self, field: &Field, color: &Color) -> bool {
+        self.attackers(field, color) != EMPTY
+    }
+
+    // checks whether neither player can possibly checkmate, i.e.
+    // K vs K, K+N vs K or kings and bishops which are all on fields of the same color
+    pub fn has_insufficient_material(&self) -> bool {
+        let pawns_rooks_queens = self.pieces_of_type(&PieceType::Pawn)
+            | self.pieces_of_type(&PieceType::Rook)
+            | self.pieces_of_type(&PieceType::Queen);
+        if pawns_rooks_queens != EMPTY {
+            return false; // pawns, rooks and queens can always mate
+        }
+
+        let knights = self.pieces_of_type(&PieceType::Knight).count_ones();
+        let bishops = self.pieces_of_type(&PieceType::Bishop);
+
+        match (knights, bishops) {
+            (0, EMPTY) | (1, EMPTY) => true,
+            (0, _) => bishops & DARK_FIELDS == EMPTY || bishops & !DARK_FIELDS == EMPTY,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn square_and_field() {
+        assert_eq!(square(&Field(0, 0)), 0);
+        assert_eq!(square(&Field(3, 4)), 28);
+        assert_eq!(field(63), Field(7, 7));
+        assert_eq!(
+            fields(bit(&Field(1, 2)) | bit(&Field(6, 5))).collect::<Vec<Field>>(),
+            vec![Field(1, 2), Field(6, 5)]
+        );
+    }
+
+    #[test]
+    fn knight_attack_table() {
+        // a knight in the corner only reaches two fields
+        assert_eq!(
+            fields(knight_attacks(&Field(0, 0))).collect::<Vec<Field>>(),
+            vec![Field(1, 2), Field(2, 1)]
+        );
+        assert_eq!(knight_attacks(&Field(3, 3)).count_ones(), 8);
+        assert_eq!(knight_attacks(&Field(7, 6)).count_ones(), 3);
+    }
+
+    #[test]
+    fn king_attack_table() {
+        assert_eq!(king_attacks(&Field(0, 0)).count_ones(), 3);
+        assert_eq!(king_attacks(&Field(0, 7)).count_ones(), 3);
+        assert_eq!(king_attacks(&Field(4, 4)).count_ones(), 8);
+        assert_eq!(king_attacks(&Field(7, 3)).count_ones(), 5);
+    }
+
+    #[test]
+    fn pawn_attack_table() {
+        assert_eq!(
+            fields(pawn_attacks(&Color::White, &Field(1, 0))).collect::<Vec<Field>>(),
+            vec![Field(2, 1)]
+        );
+        assert_eq!(
+            fields(pawn_attacks(&Color::Black, &Field(6, 4))).collect::<Vec<Field>>(),
+            vec![Field(5, 3), Field(5, 5)]
+        );
+    }
+
+    #[test]
+    fn sliding_attacks() {
+        // rook on d4, blockers on d6 and b4
+        let occupied = bit(&Field(3, 3)) | bit(&Field(5, 3)) | bit(&Field(3, 1));
+        let attacks = rook_attacks(&Field(3, 3), occupied);
+        assert_eq!(attacks.count_ones(), 3 + 2 + 2 + 4);
+        assert!(attacks & bit(&Field(5, 3)) != EMPTY);
+        assert!(attacks & bit(&Field(6, 3)) == EMPTY);
+        assert!(attacks & bit(&Field(3, 0)) == EMPTY);
+
+        // bishop on c1, blocker on e3
+        let occupied = bit(&Field(0, 2)) | bit(&Field(2, 4));
+        let attacks = bishop_attacks(&Field(0, 2), occupied);
+        assert_eq!(
+            fields(attacks).collect::<Vec<Field>>(),
+            vec![Field(1, 1), Field(1, 3), Field(2, 0), Field(2, 4)]
+        );
+    }
+
+    #[test]
+    fn between_fields() {
+        assert_eq!(
+            fields(between(&Field(0, 4), &Field(0, 7))).collect::<Vec<Field>>(),
+            vec![Field(0, 5), Field(0, 6)]
+        );
+        assert_eq!(
+            fields(between(&Field(6, 6), &Field(3, 3))).collect::<Vec<Field>>(),
+            vec![Field(4, 4), Field(5, 5)]
+        );
+        assert_eq!(between(&Field(0, 0), &Field(1, 2)), EMPTY);
+        assert_eq!(between(&Field(0, 0), &Field(0, 1)), EMPTY);
+    }
+
+    #[test]
+    fn board_place_and_remove() {
+        let mut board = Board::empty();
+        let white_rook = Piece::new(Color::White, PieceType::Rook);
+        let black_knight = Piece::new(Color::Black, PieceType::Knight);
+        board.place_piece(white_rook, &Field(0, 0));
+        assert_eq!(board.get_piece_on_field(&Field(0, 0)), white_rook);
+        assert_eq!(board.place_piece(black_knight, &Field(0, 0)), white_rook);
+        assert_eq!(board.pieces(&Color::White, &PieceType::Rook), EMPTY);
+        assert_eq!(board.remove_piece_from_field(&Field(0, 0)), black_knight);
+        assert_eq!(board.occupied(), EMPTY);
+    }
+
+    #[test]
+    fn board_attackers() {
+        let mut board = Board::empty();
+        board.place_piece(Piece::new(Color::White, PieceType::Rook), &Field(0, 4));
+        board.place_piece(Piece::new(Color::White, PieceType::Pawn), &Field(2, 3));
+        board.place_piece(Piece::new(Color::White, PieceType::Knight), &Field(1, 5));
+        board.place_piece(Piece::new(Color::Black, PieceType::Bishop), &Field(6, 1));
+
+        let attackers = board.attackers(&Field(3, 4), &Color::White);
+        assert_eq!(
+            fields(attackers).collect::<Vec<Field>>(),
+            vec![Field(0, 4), Field(1, 5), Field(2, 3)]
+        );
+        assert!(board.is_field_attacked(&Field(3, 4), &Color::Black));
+        assert!(board.is_field_attacked(&Field(2, 5), &Color::Black));
+
+        // a piece on e4 blocks the bishop
+        board.place_piece(Piece::new(Color::White, PieceType::Queen), &Field(3, 4));
+        assert!(!board.is_field_attacked(&Field(2, 5), &Color::Black));
+    }
+}
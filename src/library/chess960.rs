@@ -0,0 +1,136 @@
+use crate::library::game::State;
+
+/*
+    Chess960 (Fischer Random Chess) starts from one of 960 shuffled back ranks: the bishops stand on fields of
+    opposite colors and the king stands somewhere between the two rooks. Castling moves king and rook to the same
+    fields as in standard chess; the king castles by moving onto the rook it castles with.
+
+    The start positions are numbered 0 - 959 following Scharnagl, so 518 is the standard start position.
+*/
+impl State {
+    pub fn new_chess960(number: usize) -> State {
+        let back_rank = State::chess960_back_rank(number % 960);
+        let rank: String = back_rank.iter().collect();
+        let fen = format!(
+            "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+            rank,
+            rank.to_ascii_uppercase()
+        );
+        match State::from_fen(&fen) {
+            Ok(mut state) => {
+                state.set_chess960(true);
+                state
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn chess960_back_rank(number: usize) -> [char; 8] {
+        let mut back_rank = [' '; 8];
+
+        // the bishops go on a light and a dark field
+        back_rank[2 * (number % 4) + 1] = 'b';
+        back_rank[2 * ((number / 4) % 4)] = 'b';
+
+        // the queen and the knights take the n-th remaining empty field
+        let mut place_on_empty_field = |piece: char, n: usize| {
+            let file = (0..8)
+                .filter(|file| back_rank[*file] == ' ')
+                .nth(n)
+                .unwrap();
+            back_rank[file] = piece;
+        };
+        place_on_empty_field('q', (number / 16) % 6);
+        #[rustfmt::skip]
+        let knights = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+        let (first_knight, second_knight) = knights[number / 96];
+        place_on_empty_field('n', second_knight);
+        place_on_empty_field('n', first_knight);
+
+        // the king stands between the rooks on the last three fields
+        for piece in ['r', 'k', 'r'] {
+            place_on_empty_field(piece, 0);
+        }
+        back_rank
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::library::game::{Field, Move};
+
+    fn find_move(state: &State, start_field: Field, target_field: Field) -> Move {
+        state
+            .legal_moves_from(&start_field)
+            .into_iter()
+            .find(|chess_move| chess_move.target_field() == &target_field)
+            .unwrap()
+    }
+
+    #[test]
+    fn chess960_start_positions() {
+        assert_eq!(
+            State::new_chess960(518).to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        assert_eq!(
+            State::new_chess960(0).to_fen(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+        );
+        assert_eq!(
+            State::new_chess960(959).to_shredder_fen(),
+            "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w CAca - 0 1"
+        );
+        assert!(State::new_chess960(518).is_chess960());
+    }
+
+    #[test]
+    fn chess960_castling() {
+        // the king on b1 castles queenside by moving onto the rook on a1 and stays on c1 ...
+        let mut state =
+            State::load_game_from_fen(String::from("r4k1r/8/8/8/8/8/8/RK5R w AHah - 0 1"));
+        assert!(state.is_chess960());
+        assert_eq!(state.to_fen(), "r4k1r/8/8/8/8/8/8/RK5R w KQkq - 0 1");
+        let castling = find_move(&state, Field(0, 1), Field(0, 0));
+        state.make_move(&castling);
+        assert_eq!(state.to_fen(), "r4k1r/8/8/8/8/8/8/2KR3R b kq - 1 1");
+
+        // ... while the black king on f8 castles kingside by moving onto the rook on h8
+        let castling = find_move(&state, Field(7, 5), Field(7, 7));
+        state.make_move(&castling);
+        assert_eq!(state.to_fen(), "r4rk1/8/8/8/8/8/8/2KR3R w - - 2 2");
+    }
+
+    // positions and node counts from https://www.chessprogramming.org/Chess960_Perft_Results
+    #[test]
+    fn perft_chess960() {
+        let positions: [(&str, [u64; 3]); 3] = [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [21, 528, 12189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                [21, 807, 18002],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                [20, 479, 10471],
+            ),
+        ];
+        for (fen, node_counts) in positions.iter() {
+            let state = State::load_game_from_fen(String::from(*fen));
+            for (depth, nodes) in node_counts.iter().enumerate() {
+                assert_eq!(
+                    state.perft(depth + 1),
+                    *nodes,
+                    "{} (depth {})",
+                    fen,
+                    depth + 1
+                );
+            }
+        }
+    }
+}
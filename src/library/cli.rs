@@ -1,5 +1,5 @@
-use crate::library::game::*;
-use std::{collections::LinkedList, fmt, io};
+use crate::library::{epd::EpdRecord, game::*, pgn::PgnGame};
+use std::{fmt, fs, io, io::BufRead};
 
 // somehow the black ascii chess pieces look like white and vice versa...
 // depending on the console, they made need to be swapped (again)
@@ -19,76 +19,222 @@ const BLACK_PAWN: char = '\u{2659}';
 /*
     This is the main function for the CLI version of the game.
 
-    The game is a linked list of board states (one for each half-move). It can be initialized using a FEN string
+    The game is a tree of board states (one for each half-move). It can be initialized using a FEN string
     (e.g., "rn1qk2r/pppbbppp/5n2/4p3/N2p4/1P1P4/PBPQPPPP/R3KBNR w KQkq - 3 7") if a specific position should be
-    loaded. Otherwise a new game is created. In both cases, the game starts as a list containing one board state.
+    loaded. Otherwise a new game is created. In both cases, the game starts as a tree containing one board state.
+    A game loaded from a PGN file starts with the states of all its moves and variations instead.
 
-    The game can be played by the user by entering a move in the form:
+    The game can be played by the user by entering a move in standard algebraic notation (e.g., "e4", "Nbd7", "exd5",
+    "O-O" or "e8=Q"), in UCI notation (e.g., "e2e4" or "e7e8q") or in the form:
         <start row index><start column index><target row index><target column index>
-    For example, the starting move "e4" would be entered as "2545". In the future parsing of moves in more intuitive
-    notation may be supported.
+    For example, the starting move "e4" could also be entered as "2545". When a pawn reaches the last rank, the piece
+    it is promoted to can be appended (e.g., "7181q") - otherwise the player is asked for it.
+
+    Once a position occurred three times or fifty moves were played without a capture or pawn move, the player to
+    move may claim a draw by typing "claim". Typing "resign" resigns the game and "draw" offers a draw to the opponent.
+
+    Typing "<" and ">" takes back a move and plays it again. Playing a different move after taking one back starts a
+    variation, which is kept alongside the previous continuation. Typing "prev" and "next" switches between the
+    variations of the last move, "promote" makes the current line the mainline and "delete" removes the last move
+    together with all moves following it.
+
+    Typing "fen" prints the FEN string of the current position, which can be used to load it again later.
+    Typing "save <file>" saves the game as PGN (only "save" prints it instead).
+
+    Once the game is over, its result is announced and no more moves can be played. The other commands keep working,
+    so the game can still be looked through and saved. Typing "quit" ends the program.
 */
+pub fn run(game: GameState) {
+    play(game, &mut io::stdin().lock());
+}
+
+// the game loop, reading the commands from the given input until it ends
 #[rustfmt::skip]
-pub fn run(fen: Option<String>) {
-    let mut game = GameState { selected_field: None, active_states: LinkedList::new(), inactive_states: LinkedList::new() };
-    game.active_states.push_back(State::new(fen));
-    
+fn play(mut game: GameState, input: &mut impl BufRead) -> GameState {
+    let mut announced_result: Option<GameResult> = None;
+
     loop {
-        let current_state = game.active_states.back().unwrap();
+        let current_state = game.current_state();
 
         // start by showing the current board state
-        draw_board(current_state.position().borrow());
+        draw_board(&current_state.position());
 
-        // who to move?
-        match current_state.check_game_over() {
-            GameOver::BlackWon => {
-                println!("Checkmate, black won!");
-                return;
-            }
-            GameOver::WhiteWon => {
-                println!("Checkmate, white won!");
-                return;
-            }
-            GameOver::Stalemate => {
-                println!("Stalemate!");
-                return;
-            }
+        // is the game over (announced once, it may be reopened by taking back moves)? otherwise, who to move?
+        let result = *game.result();
+        match result {
+            Some(game_result) if result != announced_result => println!("{}", game_result),
+            Some(_) => {}
+            None => draw_who_to_move(current_state.turn()),
+        }
+        announced_result = result;
 
-            _ => draw_who_to_move(current_state.turn()),
+        match game.claimable_draw() {
+            Some(Termination::ThreefoldRepetition) => println!("The position occurred three times, type 'claim' to claim a draw."),
+            Some(Termination::FiftyMoveRule) => println!("Fifty moves without a capture or pawn move, type 'claim' to claim a draw."),
+            _ => {}
         }
 
         // get input from player
-        let mut move_string = String::new();
-        io::stdin().read_line(&mut move_string).unwrap();
+        let move_string = match read_line(input) {
+            Some(move_string) => move_string,
+            None => return game,
+        };
 
         // execute the move according to the players input.
-        // the resulting state is appended to the active_states list.
+        // the resulting state is added to the game tree.
         match move_string.trim() {
             ">" => {
-                if game.inactive_states.len() == 0 {
+                if !game.redo() {
                     println!("No moves to redo!");
-                    continue;
                 }
-                let next_state = game.inactive_states.pop_back().unwrap();
-                game.active_states.push_back(next_state);
             } 
             "<" => {
-                if game.active_states.len() == 1 {
+                if !game.undo() {
                     println!("No moves to undo!");
-                    continue;
                 }
-                let next_state = game.active_states.pop_back().unwrap();
-                game.inactive_states.push_back(next_state);
+            }
+            "prev" => {
+                if !game.previous_variation() {
+                    println!("There is no previous variation!");
+                }
+            }
+            "next" => {
+                if !game.next_variation() {
+                    println!("There is no next variation!");
+                }
+            }
+            "promote" => {
+                if !game.promote_variation() {
+                    println!("The current line already is the mainline!");
+                }
+            }
+            "delete" => {
+                if !game.delete_variation() {
+                    println!("No moves to delete!");
+                }
+            }
+            "claim" => {
+                if !game.claim_draw() {
+                    println!("There is no draw to claim!");
+                }
+            }
+            "quit" => return game,
+            "fen" => println!("{}", current_state.to_fen()),
+            command if command == "save" || command.starts_with("save ") => {
+                let pgn = PgnGame::from_game_state(&game).to_pgn();
+                match command["save".len()..].trim() {
+                    "" => print!("{}", pgn),
+                    path => match fs::write(path, pgn) {
+                        Ok(_) => println!("The game was saved to {}.", path),
+                        Err(e) => println!("The game couldn't be saved: {}", e),
+                    },
+                }
+            }
+            "resign" => {
+                let player = *current_state.turn();
+                if !game.resign(&player) {
+                    println!("The game is already over!");
+                }
+            }
+            "draw" if game.is_over() => println!("The game is already over!"),
+            "draw" => {
+                let opponent = *current_state.turn_rev();
+                if read_draw_acceptance(&opponent, input) {
+                    game.agree_draw();
+                } else {
+                    println!("The draw offer was declined.");
+                }
             }
             _ => {
-                let new_state = State::perform_turn_from_input(move_string, current_state);
+                let chess_move = match State::move_from_input(move_string.clone(), current_state) {
+                    Err(MoveError::PromotionRequired) => {
+                        let promotion = read_promotion_piece(input);
+                        State::move_from_input(format!("{}{}", move_string.trim(), promotion), current_state)
+                    }
+                    chess_move => chess_move,
+                };
+                if let Ok(chess_move) = &chess_move {
+                    if chess_move.is_capture() && current_state.see(chess_move) < 0 {
+                        println!("This capture loses material!");
+                    }
+                }
+                let new_state = chess_move.map(|chess_move| current_state.execute_move(&chess_move));
                 handle_state(new_state, &mut game);
             }
         }
     }
 }
 
-fn draw_board(position: Ref<Position>) {
+/*
+    Prints the number of leaf nodes below each legal move (divide) and their total (perft) for the given depth.
+    The moves are shown in UCI notation (e.g., "e2e4"), so the output can be compared to the one of other engines.
+*/
+pub fn run_perft(state: State, depth: usize) {
+    let divide = state.divide(depth);
+    for (chess_move, nodes) in divide.iter() {
+        println!("{}: {}", chess_move.to_uci(), nodes);
+    }
+
+    // the root itself is the only node at depth 0
+    let total: u64 = match depth {
+        0 => 1,
+        _ => divide.iter().map(|(_, nodes)| nodes).sum(),
+    };
+
+    println!("\nNodes searched: {}", total);
+}
+
+/*
+    Goes through the positions of an EPD test suite and reports which of them are solved by the given moves, one
+    for each position (in SAN or UCI notation, an empty line or "-" if there is none). A position is solved if its
+    move is one of the best moves ("bm") and none of the moves to avoid ("am").
+*/
+pub fn run_epd(records: &[EpdRecord], answers: &[String]) {
+    let mut solved = 0;
+    for (index, record) in records.iter().enumerate() {
+        let name = match record.id() {
+            Some(id) => String::from(id),
+            None => format!("#{}", index + 1),
+        };
+        let answer = answers.get(index).map(|answer| answer.trim()).unwrap_or("");
+        let state = record.state();
+        let chess_move = match answer {
+            "" | "-" => None,
+            answer if State::is_uci(answer) => state.parse_uci(answer).ok(),
+            answer => state.parse_san(answer).ok(),
+        };
+
+        let expected: Vec<String> = record
+            .best_moves()
+            .iter()
+            .map(|chess_move| chess_move.to_san(state))
+            .collect();
+        let report = match chess_move {
+            Some(chess_move) if record.is_solved_by(&chess_move) => {
+                solved += 1;
+                format!("{} solved", chess_move.to_san(state))
+            }
+            Some(chess_move) if record.avoid_moves().contains(&chess_move) => {
+                format!(
+                    "{} not solved, the move should be avoided",
+                    chess_move.to_san(state)
+                )
+            }
+            Some(chess_move) => format!(
+                "{} not solved, expected {}",
+                chess_move.to_san(state),
+                expected.join(" ")
+            ),
+            None if answer.is_empty() || answer == "-" => String::from("no move given"),
+            None => format!("{} is not a legal move", answer),
+        };
+        println!("{}: {}", name, report);
+    }
+
+    println!("\nSolved {} of {} positions.", solved, records.len());
+}
+
+fn draw_board(position: &Position) {
     let split: Vec<&str> = position.split();
 
     println!("\n   1 2 3 4 5 6 7 8");
@@ -138,6 +284,31 @@ fn draw_board(position: Ref<Position>) {
     println!("   1 2 3 4 5 6 7 8");
 }
 
+// the next line of the input, none once it ended
+fn read_line(input: &mut impl BufRead) -> Option<String> {
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line),
+    }
+}
+
+fn read_promotion_piece(input: &mut impl BufRead) -> String {
+    println!("Promote to (q)ueen, (r)ook, (b)ishop or k(n)ight?");
+    let promotion = read_line(input).unwrap_or_default();
+    String::from(promotion.trim())
+}
+
+fn read_draw_acceptance(opponent: &Color, input: &mut impl BufRead) -> bool {
+    match *opponent {
+        Color::Black => println!("Black, do you accept the draw offer? (y/n)"),
+        Color::White => println!("White, do you accept the draw offer? (y/n)"),
+        _ => {}
+    }
+    let answer = read_line(input).unwrap_or_default();
+    answer.trim() == "y"
+}
+
 fn draw_who_to_move(turn: &Color) {
     match *turn {
         Color::Black => println!("Black to move..."),
@@ -171,3 +342,114 @@ impl fmt::Display for Piece {
         }
     }
 }
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (winner, loser) = match self.outcome() {
+            Outcome::WhiteWon => ("white", "Black"),
+            Outcome::BlackWon => ("black", "White"),
+            Outcome::Draw => ("nobody", "Nobody"),
+        };
+        let score = self.outcome().score_string();
+
+        match self.termination() {
+            Termination::Checkmate => write!(f, "Checkmate, {} won! ({})", winner, score),
+            Termination::Stalemate => write!(f, "Stalemate! ({})", score),
+            Termination::ThreefoldRepetition => {
+                write!(f, "Draw by threefold repetition! ({})", score)
+            }
+            Termination::FivefoldRepetition => {
+                write!(f, "Draw by fivefold repetition! ({})", score)
+            }
+            Termination::FiftyMoveRule => write!(f, "Draw by the fifty-move rule! ({})", score),
+            Termination::SeventyFiveMoveRule => {
+                write!(f, "Draw by the seventy-five-move rule! ({})", score)
+            }
+            Termination::InsufficientMaterial => {
+                write!(f, "Draw by insufficient material! ({})", score)
+            }
+            Termination::Resignation => {
+                write!(f, "{} resigned, {} won! ({})", loser, winner, score)
+            }
+            Termination::Agreement => write!(f, "Draw agreed! ({})", score),
+            Termination::TimeForfeit => {
+                write!(f, "{} lost on time, {} won! ({})", loser, winner, score)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn play_input(game: GameState, input: &str) -> GameState {
+        play(game, &mut input.as_bytes())
+    }
+
+    #[test]
+    fn game_over_keeps_commands() {
+        // moves are rejected after the resignation, but the game can still be taken back
+        let game = play_input(GameState::new(State::new(None)), "e4\nresign\ne5\nresign\n");
+        assert_eq!(
+            game.result(),
+            &Some(GameResult::win(&Color::White, Termination::Resignation))
+        );
+        assert_eq!(game.tree().depth(), 1);
+
+        let game = play_input(game, "<\nd4\n");
+        assert_eq!(game.result(), &None);
+        assert_eq!(game.tree().depth(), 1);
+
+        // a declined draw offer, an accepted one and nothing after quitting
+        let game = play_input(game, "draw\nn\ndraw\ny\nquit\n<\n");
+        assert_eq!(
+            game.result(),
+            &Some(GameResult::draw(Termination::Agreement))
+        );
+    }
+
+    #[test]
+    fn finished_pgn_game() {
+        let pgn = "[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0";
+        let game = PgnGame::from_pgn(pgn).unwrap()[0].game_state();
+        assert!(game.is_over());
+
+        // the game can be stepped through, but not continued
+        let game = play_input(game, "Nc6\n<\n<\nfen\n");
+        assert_eq!(game.tree().depth(), 1);
+        assert_eq!(game.result(), &None);
+
+        let game = play_input(game, ">\n>\n>\n");
+        assert_eq!(game.tree().depth(), 3);
+        assert_eq!(
+            game.result(),
+            &Some(GameResult::win(&Color::White, Termination::Resignation))
+        );
+    }
+
+    #[test]
+    fn save_finished_game() {
+        let path = std::env::temp_dir().join("rust_chess_cli_save.pgn");
+        let save = format!("save {}\n", path.display());
+
+        play_input(
+            GameState::new(State::new(None)),
+            &format!("f3\ne5\ng4\nQh4\n{}", save),
+        );
+        let pgn = fs::read_to_string(&path).unwrap();
+        assert!(pgn.contains("[Result \"0-1\"]"));
+        assert!(pgn.ends_with("\n\n1. f3 e5 2. g4 Qh4# 0-1\n\n"));
+
+        play_input(
+            GameState::new(State::new(None)),
+            &format!("e4\nresign\n{}", save),
+        );
+        let pgn = fs::read_to_string(&path).unwrap();
+        assert!(pgn.contains("[Result \"1-0\"]"));
+        assert!(pgn.ends_with("\n\n1. e4 1-0\n\n"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,8 +1,11 @@
-use std::{env, fmt};
+use crate::library::{epd::EpdRecord, game::State, pgn::PgnGame};
+use std::{error::Error, fmt, fs, time::SystemTime};
 
 pub enum UiType {
     CLI,
     GUI,
+    Perft(usize),
+    Epd(Vec<EpdRecord>, Vec<String>),
 }
 
 impl fmt::Display for UiType {
@@ -10,6 +13,8 @@ impl fmt::Display for UiType {
         match *self {
             UiType::CLI => write!(f, "command line"),
             UiType::GUI => write!(f, "graphical interface (OpenGL)"),
+            UiType::Perft(depth) => write!(f, "perft (depth {})", depth),
+            UiType::Epd(ref records, _) => write!(f, "EPD suite ({} positions)", records.len()),
         }
     }
 }
@@ -17,21 +22,144 @@ impl fmt::Display for UiType {
 pub struct Config {
     pub ui_type: UiType,
     pub fen: Option<String>,
+    // standard start positions are valid Chess960 positions as well, so the mode can't
+    // always be told from the FEN string
+    pub chess960: bool,
+    pub pgn: Option<PgnGame>,
 }
 
 impl Config {
-    pub fn new(mut args: env::Args) -> Result<Config, &'static str> {
+    pub fn new(mut args: impl Iterator<Item = String>) -> Result<Config, Box<dyn Error>> {
         args.next(); // skip the program name
 
         let ui_type = match args.next() {
             Some(arg) if arg == "cli" => UiType::CLI,
             Some(arg) if arg == "gui" => UiType::GUI,
-            Some(_) => return Err("Please enter a valid UI type (cli / gui)."),
-            None => return Err("Please enter a valid UI type (cli / gui)."),
+            Some(arg) if arg == "perft" => match args.next().map(|depth| depth.parse::<usize>()) {
+                Some(Ok(depth)) => UiType::Perft(depth),
+                _ => return Err("Please enter a valid perft depth (e.g., perft 4).".into()),
+            },
+            // the suite and the moves to check, one per line
+            Some(arg) if arg == "epd" => {
+                let (suite, answers) = match (args.next(), args.next()) {
+                    (Some(suite), Some(answers)) => (suite, answers),
+                    _ => return Err("Please enter an EPD suite and a file with its moves.".into()),
+                };
+                let records = match fs::read_to_string(suite) {
+                    Ok(suite) => match EpdRecord::from_epd_suite(&suite) {
+                        Ok(records) => records,
+                        Err(e) => return Err(Box::new(e)),
+                    },
+                    Err(e) => return Err(Box::new(e)),
+                };
+                let answers = match fs::read_to_string(answers) {
+                    Ok(answers) => answers.lines().map(String::from).collect(),
+                    Err(e) => return Err(Box::new(e)),
+                };
+                UiType::Epd(records, answers)
+            }
+            Some(_) => return Err("Please enter a valid UI type (cli / gui / perft / epd).".into()),
+            None => return Err("Please enter a valid UI type (cli / gui / perft / epd).".into()),
         };
 
-        let fen = args.next();
+        // either a game from a PGN file (its first one), a Chess960 start position
+        // (random, unless its number is given) or a FEN string
+        let mut pgn = None;
+        let mut chess960 = false;
+        let fen = match args.next() {
+            Some(arg) if arg == "--pgn" => {
+                let pgn_string = match args.next().map(fs::read_to_string) {
+                    Some(Ok(pgn_string)) => pgn_string,
+                    Some(Err(e)) => return Err(Box::new(e)),
+                    None => return Err("Please enter the path of a PGN file.".into()),
+                };
+                let games = match PgnGame::from_pgn(&pgn_string) {
+                    Ok(games) => games,
+                    Err(e) => return Err(Box::new(e)),
+                };
+                pgn = games.into_iter().next();
+                None
+            }
+            Some(arg) if arg == "960" => {
+                chess960 = true;
+                match args.next().map(|number| number.parse::<usize>()) {
+                    Some(Ok(number)) if number < 960 => Some(State::new_chess960(number).to_fen()),
+                    Some(_) => {
+                        return Err("Please enter a valid Chess960 position (0 - 959).".into())
+                    }
+                    None => Some(State::new_chess960(Config::random_chess960_number()).to_fen()),
+                }
+            }
+            fen => fen,
+        };
+
+        // make sure the position can be loaded before starting the game
+        if let Some(fen) = &fen {
+            if let Err(e) = State::from_fen(fen) {
+                return Err(Box::new(e));
+            }
+        }
+
+        Ok(Config {
+            ui_type,
+            fen,
+            chess960,
+            pgn,
+        })
+    }
+
+    // the position the game starts from (unless it is loaded from a PGN file)
+    pub fn state(&self) -> State {
+        let mut state = State::new(self.fen.clone());
+        if self.chess960 {
+            state.set_chess960(true);
+        }
+        state
+    }
+
+    fn random_chess960_number() -> usize {
+        match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(duration) => duration.subsec_nanos() as usize % 960,
+            Err(_) => 518,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn config(args: &[&str]) -> Config {
+        let args = ["rust_chess"]
+            .iter()
+            .chain(args.iter())
+            .map(|arg| String::from(*arg));
+        match Config::new(args) {
+            Ok(config) => config,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn chess960() {
+        // the standard start position keeps the Chess960 castling rules
+        let mut state = config(&["cli", "960", "518"]).state();
+        assert!(state.is_chess960());
+        assert_eq!(state.board(), State::new(None).board());
+
+        for san in ["Nf3", "Nf6", "g3", "g6", "Bg2", "Bg7"] {
+            state = state.execute_move(&state.parse_san(san).unwrap());
+        }
+        // the king castles by moving onto the rook
+        let castling = state.parse_uci("e1h1").unwrap();
+        assert!(castling.is_castling());
+        assert!(state.parse_uci("e1g1").is_err());
 
-        Ok(Config { ui_type, fen })
+        assert!(!config(&["cli"]).state().is_chess960());
+        assert!(!config(&["cli", "960", "0"])
+            .state()
+            .to_fen()
+            .starts_with("rnbqkbnr"));
     }
 }
@@ -0,0 +1,333 @@
+use crate::library::game::{FenError, Move, State};
+use std::{error::Error, fmt};
+
+/*
+    Extended Position Description (EPD) is used for test suites like WAC or STS. Each line holds the first four
+    fields of a FEN string (position, turn, castling and en passant) followed by operations, e.g.:
+
+        2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+
+    An operation consists of its opcode and operands and ends with ";". Operands containing spaces (or ";") are
+    quoted. The standard opcodes used here are:
+        bm / am     the best moves / the moves to avoid (in SAN)
+        id          the name of the position
+        c0 - c9     comments
+        dm          the number of moves until a direct mate
+        pv          the predicted variation (in SAN)
+        hmvc / fmvn the halfmove clock and fullmove number, which complete the FEN string
+
+    The moves are checked while reading a position. Other opcodes are kept and can be looked up by their name.
+*/
+#[derive(PartialEq, Clone, Debug)]
+pub struct EpdRecord {
+    state: State,
+    operations: Vec<(String, Vec<String>)>,
+    best_moves: Vec<Move>,
+    avoid_moves: Vec<Move>,
+    pv: Vec<Move>,
+    direct_mate: Option<usize>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum EpdError {
+    WrongNumberOfFields(usize),
+    InvalidFen(FenError),
+    UnterminatedString,
+    InvalidOperation(String),
+    IllegalMove(String),
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpdError::WrongNumberOfFields(number) => write!(
+                f,
+                "Invalid EPD, the position has {} instead of 4 fields",
+                number
+            ),
+            EpdError::InvalidFen(e) => write!(f, "Invalid EPD, {}", e),
+            EpdError::UnterminatedString => write!(f, "Invalid EPD, a string is missing its '\"'"),
+            EpdError::InvalidOperation(operation) => {
+                write!(f, "Invalid EPD, can't read the operation '{}'", operation)
+            }
+            EpdError::IllegalMove(chess_move) => {
+                write!(f, "Invalid EPD, the move {} can't be played", chess_move)
+            }
+        }
+    }
+}
+
+impl Error for EpdError {}
+
+impl EpdRecord {
+    pub fn from_epd(epd: &str) -> Result<EpdRecord, EpdError> {
+        // the four fields of the position come first
+        let mut fields = Vec::new();
+        let mut rest = epd.trim();
+        while fields.len() < 4 && !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        if fields.len() != 4 {
+            return Err(EpdError::WrongNumberOfFields(fields.len()));
+        }
+
+        let operations = EpdRecord::read_operations(rest)?;
+        let clock = |opcode: &str, default: &str| -> String {
+            match operations.iter().find(|(name, _)| name == opcode) {
+                Some((_, operands)) if operands.len() == 1 => operands[0].clone(),
+                _ => String::from(default),
+            }
+        };
+        let fen = format!(
+            "{} {} {}",
+            fields.join(" "),
+            clock("hmvc", "0"),
+            clock("fmvn", "1")
+        );
+        let state = match State::from_fen(&fen) {
+            Ok(state) => state,
+            Err(e) => return Err(EpdError::InvalidFen(e)),
+        };
+
+        let mut record = EpdRecord {
+            state,
+            operations,
+            best_moves: Vec::new(),
+            avoid_moves: Vec::new(),
+            pv: Vec::new(),
+            direct_mate: None,
+        };
+        record.best_moves = record.read_moves("bm", false)?;
+        record.avoid_moves = record.read_moves("am", false)?;
+        record.pv = record.read_moves("pv", true)?;
+        record.direct_mate = match record.operation("dm") {
+            Some(operands) => {
+                let operands = operands.join(" ");
+                match operands.parse::<usize>() {
+                    Ok(number) => Some(number),
+                    Err(_) => return Err(EpdError::InvalidOperation(format!("dm {}", operands))),
+                }
+            }
+            None => None,
+        };
+        Ok(record)
+    }
+
+    // a suite has one position per line, empty lines are skipped
+    pub fn from_epd_suite(suite: &str) -> Result<Vec<EpdRecord>, EpdError> {
+        suite
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(EpdRecord::from_epd)
+            .collect()
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn operations(&self) -> &Vec<(String, Vec<String>)> {
+        &self.operations
+    }
+
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| &operands[..])
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.single_operand("id")
+    }
+
+    // the comments c0 - c9
+    pub fn comment(&self, number: usize) -> Option<&str> {
+        self.single_operand(&format!("c{}", number))
+    }
+
+    pub fn best_moves(&self) -> &Vec<Move> {
+        &self.best_moves
+    }
+
+    pub fn avoid_moves(&self) -> &Vec<Move> {
+        &self.avoid_moves
+    }
+
+    pub fn pv(&self) -> &Vec<Move> {
+        &self.pv
+    }
+
+    pub fn direct_mate(&self) -> Option<usize> {
+        self.direct_mate
+    }
+
+    // the move has to be one of the best moves (if there are any) and none of the moves to avoid
+    pub fn is_solved_by(&self, chess_move: &Move) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(chess_move))
+            && !self.avoid_moves.contains(chess_move)
+    }
+
+    fn single_operand(&self, opcode: &str) -> Option<&str> {
+        match self.operation(opcode) {
+            Some([operand]) => Some(operand),
+            _ => None,
+        }
+    }
+
+    // the moves of bm and am are all played from the position, the ones of pv one after another
+    fn read_moves(&self, opcode: &str, in_sequence: bool) -> Result<Vec<Move>, EpdError> {
+        let mut state = self.state.clone();
+        let mut moves = Vec::new();
+        for san in self.operation(opcode).unwrap_or(&[]) {
+            let chess_move = match state.parse_san(san) {
+                Ok(chess_move) => chess_move,
+                Err(_) => return Err(EpdError::IllegalMove(format!("{} {}", opcode, san))),
+            };
+            if in_sequence {
+                state.make_move(&chess_move);
+            }
+            moves.push(chess_move);
+        }
+        Ok(moves)
+    }
+
+    fn read_operations(operations: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+        let mut result = Vec::new();
+        let mut words: Vec<String> = Vec::new();
+        let mut word = String::new();
+        let mut chars = operations.chars();
+
+        let flush = |word: &mut String, words: &mut Vec<String>| {
+            if !word.is_empty() {
+                words.push(word.clone());
+                word.clear();
+            }
+        };
+
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    flush(&mut word, &mut words);
+                    let mut string = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some(c) => string.push(c),
+                            None => return Err(EpdError::UnterminatedString),
+                        }
+                    }
+                    words.push(string);
+                }
+                ';' => {
+                    flush(&mut word, &mut words);
+                    if words.is_empty() {
+                        return Err(EpdError::InvalidOperation(String::from(";")));
+                    }
+                    let opcode = words.remove(0);
+                    result.push((opcode, words.clone()));
+                    words.clear();
+                }
+                c if c.is_whitespace() => flush(&mut word, &mut words),
+                c => word.push(c),
+            }
+        }
+
+        // the last operation is missing its ";"
+        flush(&mut word, &mut words);
+        if !words.is_empty() {
+            return Err(EpdError::InvalidOperation(words.join(" ")));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const WAC_001: &str =
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";";
+
+    #[test]
+    fn from_epd() {
+        let record = EpdRecord::from_epd(WAC_001).unwrap();
+        assert_eq!(
+            record.state().to_fen(),
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+        );
+        assert_eq!(record.id(), Some("WAC.001"));
+        assert_eq!(record.best_moves().len(), 1);
+        assert_eq!(record.best_moves()[0].to_san(record.state()), "Qg6");
+        assert!(record.avoid_moves().is_empty());
+        assert_eq!(record.direct_mate(), None);
+        assert_eq!(record.operations().len(), 2);
+    }
+
+    #[test]
+    fn from_epd_operations() {
+        let epd = "4k3/8/4K3/8/8/8/8/R7 w - - dm 1; pv Ra8#; am Ra7 Rb1; c0 \"mate; in one\"; \
+                   hmvc 12; fmvn 40; acd 3;";
+        let record = EpdRecord::from_epd(epd).unwrap();
+        assert_eq!(record.state().to_fen(), "4k3/8/4K3/8/8/8/8/R7 w - - 12 40");
+        assert_eq!(record.direct_mate(), Some(1));
+        assert_eq!(record.pv().len(), 1);
+        assert_eq!(record.avoid_moves().len(), 2);
+        assert_eq!(record.comment(0), Some("mate; in one"));
+        assert_eq!(record.comment(1), None);
+        assert_eq!(record.operation("acd"), Some(&[String::from("3")][..]));
+        assert_eq!(record.id(), None);
+
+        // without best moves, every move except the ones to avoid solves the position
+        let state = record.state();
+        assert!(record.is_solved_by(&state.parse_san("Ra8").unwrap()));
+        assert!(!record.is_solved_by(&state.parse_san("Ra7").unwrap()));
+    }
+
+    #[test]
+    fn from_epd_suite() {
+        let suite = format!(
+            "{}\n\n{}\n",
+            WAC_001,
+            "r1b1kb1r/3q1ppp/pBp1pn2/8/Np3P2/5B2/PPP3PP/R2Q1RK1 w kq - bm Bxc6; id \"WAC.002\";"
+        );
+        let records = EpdRecord::from_epd_suite(&suite).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].id(), Some("WAC.002"));
+
+        let record = &records[0];
+        assert!(record.is_solved_by(&record.state().parse_san("Qg6").unwrap()));
+        assert!(!record.is_solved_by(&record.state().parse_san("Qh4").unwrap()));
+    }
+
+    #[test]
+    fn from_epd_errors() {
+        assert_eq!(
+            EpdRecord::from_epd("8/8/8/8 w -"),
+            Err(EpdError::WrongNumberOfFields(3))
+        );
+        assert_eq!(
+            EpdRecord::from_epd("8/8/8/8/8/8/8 w - - id \"x\";"),
+            Err(EpdError::InvalidFen(FenError::WrongNumberOfRanks(7)))
+        );
+        assert_eq!(
+            EpdRecord::from_epd("4k3/8/8/8/8/8/8/4K3 w - - id \"x;"),
+            Err(EpdError::UnterminatedString)
+        );
+        assert_eq!(
+            EpdRecord::from_epd("4k3/8/8/8/8/8/8/4K3 w - - id x"),
+            Err(EpdError::InvalidOperation(String::from("id x")))
+        );
+        assert_eq!(
+            EpdRecord::from_epd("4k3/8/8/8/8/8/8/4K3 w - - bm Kf3;"),
+            Err(EpdError::IllegalMove(String::from("bm Kf3")))
+        );
+        assert_eq!(
+            EpdRecord::from_epd("4k3/8/8/8/8/8/8/4K3 w - - dm x;"),
+            Err(EpdError::InvalidOperation(String::from("dm x")))
+        );
+    }
+}
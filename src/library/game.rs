@@ -1,11 +1,81 @@
-pub use std::cell::Ref;
-use std::{cell::RefCell, cmp::max, cmp::PartialEq, collections::LinkedList};
+use crate::library::bitboard::{self, Board};
+#[cfg(feature = "serde")]
+use crate::library::serialization::SerializedState;
+use crate::library::tree::GameTree;
+use crate::library::zobrist;
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+use std::{cmp::max, cmp::PartialEq, error::Error, fmt};
 
-pub enum GameOver {
-    No,
+#[derive(PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Outcome {
     WhiteWon,
     BlackWon,
+    Draw,
+}
+
+impl Outcome {
+    // the result token as used in PGN
+    pub fn score_string(&self) -> String {
+        match self {
+            Outcome::WhiteWon => String::from("1-0"),
+            Outcome::BlackWon => String::from("0-1"),
+            Outcome::Draw => String::from("1/2-1/2"),
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Termination {
+    Checkmate,
     Stalemate,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+    Resignation,
+    Agreement,
+    TimeForfeit,
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameResult {
+    outcome: Outcome,
+    termination: Termination,
+}
+
+impl GameResult {
+    pub fn new(outcome: Outcome, termination: Termination) -> GameResult {
+        GameResult {
+            outcome,
+            termination,
+        }
+    }
+
+    pub fn win(winner: &Color, termination: Termination) -> GameResult {
+        let outcome = match *winner {
+            Color::White => Outcome::WhiteWon,
+            Color::Black => Outcome::BlackWon,
+            Color::None => panic!("A game can only be won by a player!"),
+        };
+        GameResult::new(outcome, termination)
+    }
+
+    pub fn draw(termination: Termination) -> GameResult {
+        GameResult::new(Outcome::Draw, termination)
+    }
+
+    pub fn outcome(&self) -> &Outcome {
+        &self.outcome
+    }
+
+    pub fn termination(&self) -> &Termination {
+        &self.termination
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -24,30 +94,189 @@ pub enum MoveError {
     NotMovingOutOfCheck,
     CastlingThroughCheck,
     CastlingNotAvailable,
+    PromotionRequired,
+    InvalidPromotion,
+    InvalidNotation,
+    AmbiguousMove,
 }
 
+#[derive(PartialEq, Clone, Debug)]
+pub enum FenError {
+    WrongNumberOfFields(usize),
+    WrongNumberOfRanks(usize),
+    BadRankLength(usize),
+    UnknownPiece(char),
+    InvalidTurn(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveClock(String),
+    InvalidPosition(PositionError),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::WrongNumberOfFields(n) => {
+                write!(f, "Invalid FEN string, expected 6 fields but found {}", n)
+            }
+            FenError::WrongNumberOfRanks(n) => {
+                write!(f, "Invalid FEN string, expected 8 ranks but found {}", n)
+            }
+            FenError::BadRankLength(rank) => {
+                write!(f, "Invalid FEN string, rank {} doesn't have 8 files", rank)
+            }
+            FenError::UnknownPiece(c) => write!(f, "Invalid FEN string, unknown piece '{}'", c),
+            FenError::InvalidTurn(value) => write!(f, "Invalid FEN string, turn value: {}", value),
+            FenError::InvalidCastling(value) => write!(
+                f,
+                "Invalid FEN string, castle availability value: {}",
+                value
+            ),
+            FenError::InvalidEnPassant(value) => {
+                write!(f, "Invalid FEN string, en-passant value: {}", value)
+            }
+            FenError::InvalidHalfmoveClock(value) => {
+                write!(f, "Invalid FEN string, halfmove clock value: {}", value)
+            }
+            FenError::InvalidFullmoveClock(value) => {
+                write!(f, "Invalid FEN string, fullmove clock value: {}", value)
+            }
+            FenError::InvalidPosition(e) => write!(f, "Invalid FEN string, {}", e),
+        }
+    }
+}
+
+impl Error for FenError {}
+
+// setups that can't occur in a game (see 'State::validate')
+#[derive(PartialEq, Clone, Debug)]
+pub enum PositionError {
+    WrongNumberOfKings(Color, usize),
+    PawnOnBackRank(Field),
+    OpponentInCheck,
+    ImpossibleCastling(Color, usize),
+    ImpossibleEnPassant(Field),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let field_name =
+            |field: &Field| format!("{}{}", (b'a' + field.1 as u8) as char, field.0 + 1);
+        let color_name = |color: &Color| match color {
+            Color::White => "white",
+            _ => "black",
+        };
+        match self {
+            PositionError::WrongNumberOfKings(color, n) => {
+                write!(f, "position with {} {} kings instead of one", n, color_name(color))
+            }
+            PositionError::PawnOnBackRank(field) => {
+                write!(f, "position with a pawn on {}", field_name(field))
+            }
+            PositionError::OpponentInCheck => {
+                write!(f, "position with the player not to move in check")
+            }
+            PositionError::ImpossibleCastling(color, file) => write!(
+                f,
+                "position where {} can't castle with the rook on the {}-file",
+                color_name(color),
+                (b'a' + *file as u8) as char
+            ),
+            PositionError::ImpossibleEnPassant(field) => {
+                write!(f, "position where en passant on {} isn't possible", field_name(field))
+            }
+        }
+    }
+}
+
+impl Error for PositionError {}
+
 #[derive(PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     Black,
     White,
     None,
 }
 
-#[derive(Clone, Debug)]
+// the file of the rook each side may still castle with
+// (the corner files in standard chess, any file the rook started on in Chess960)
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CastleAvailability {
-    white_king: bool,
-    white_queen: bool,
-    black_king: bool,
-    black_queen: bool,
+    white_king: Option<usize>,
+    white_queen: Option<usize>,
+    black_king: Option<usize>,
+    black_queen: Option<usize>,
+}
+
+impl CastleAvailability {
+    pub fn none() -> CastleAvailability {
+        CastleAvailability {
+            white_king: None,
+            white_queen: None,
+            black_king: None,
+            black_queen: None,
+        }
+    }
+
+    pub fn rook_file(&self, color: &Color, kingside: bool) -> Option<usize> {
+        match (color, kingside) {
+            (Color::White, true) => self.white_king,
+            (Color::White, false) => self.white_queen,
+            (Color::Black, true) => self.black_king,
+            (Color::Black, false) => self.black_queen,
+            (Color::None, _) => None,
+        }
+    }
+
+    fn rook_file_mut(&mut self, color: &Color, kingside: bool) -> Option<&mut Option<usize>> {
+        match (color, kingside) {
+            (Color::White, true) => Some(&mut self.white_king),
+            (Color::White, false) => Some(&mut self.white_queen),
+            (Color::Black, true) => Some(&mut self.black_king),
+            (Color::Black, false) => Some(&mut self.black_queen),
+            (Color::None, _) => None,
+        }
+    }
+
+    // a rook leaving its starting field or being captured on it can't castle anymore
+    fn remove_rook(&mut self, field: &Field) {
+        let color = match field.0 {
+            0 => Color::White,
+            7 => Color::Black,
+            _ => return,
+        };
+        for kingside in [true, false] {
+            if let Some(rook_file) = self.rook_file_mut(&color, kingside) {
+                if *rook_file == Some(field.1) {
+                    *rook_file = None;
+                }
+            }
+        }
+    }
+}
+
+// the fields king and rook move to when castling
+#[derive(PartialEq, Clone, Debug)]
+struct Castling {
+    king_target: Field,
+    rook_start: Field,
+    rook_target: Field,
 }
 
 #[derive(PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Piece {
     color: Color,
     piecetype: PieceType,
 }
 
 impl Piece {
+    pub fn new(color: Color, piecetype: PieceType) -> Piece {
+        Piece { color, piecetype }
+    }
     pub fn color(&self) -> &Color {
         &self.color
     }
@@ -57,6 +286,7 @@ impl Piece {
 }
 
 #[derive(PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceType {
     Rook,
     Knight,
@@ -67,15 +297,34 @@ pub enum PieceType {
     None,
 }
 
+impl PieceType {
+    // the material value in centipawns (the king can't be exchanged)
+    pub fn value(&self) -> i32 {
+        match self {
+            PieceType::Pawn => 100,
+            PieceType::Knight => 300,
+            PieceType::Bishop => 300,
+            PieceType::Rook => 500,
+            PieceType::Queen => 900,
+            PieceType::King | PieceType::None => 0,
+        }
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Field(pub usize, pub usize);
 
+#[derive(PartialEq, Clone, Debug)]
 pub struct PieceInstance {
     piece: Piece,
     field: Field,
 }
 
 impl PieceInstance {
+    pub fn new(piece: Piece, field: Field) -> PieceInstance {
+        PieceInstance { piece, field }
+    }
     pub fn piece(&self) -> &Piece {
         &self.piece
     }
@@ -84,22 +333,231 @@ impl PieceInstance {
     }
 }
 
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RawGameState")
+)]
 pub struct GameState {
     pub selected_field: Option<(usize, usize)>,
-    pub active_states: LinkedList<State>,
-    pub inactive_states: LinkedList<State>,
+    pub pending_promotion: Option<String>,
+    pub draw_offered: bool,
+    tree: GameTree,
+    result: Option<GameResult>,
+    // the PGN tags of a loaded game (e.g. the names of the players), written back when it is saved
+    tags: Vec<(String, String)>,
+}
+
+impl GameState {
+    pub fn new(initial_state: State) -> GameState {
+        GameState::from_tree(GameTree::new(initial_state))
+    }
+
+    pub fn from_tree(tree: GameTree) -> GameState {
+        let mut game = GameState {
+            selected_field: None,
+            pending_promotion: None,
+            draw_offered: false,
+            tree,
+            result: None,
+            tags: Vec::new(),
+        };
+        game.update_result();
+        game
+    }
+
+    pub fn tree(&self) -> &GameTree {
+        &self.tree
+    }
+
+    pub fn current_state(&self) -> &State {
+        self.tree.current_state()
+    }
+
+    pub fn tags(&self) -> &Vec<(String, String)> {
+        &self.tags
+    }
+
+    pub fn set_tags(&mut self, tags: Vec<(String, String)>) {
+        self.tags = tags;
+    }
+
+    // continues the game with the state after a move
+    // (moves that were taken back are kept as a variation)
+    pub fn push_state(&mut self, state: State) {
+        self.tree.push(state);
+        self.update_result();
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.navigate(GameTree::back)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.navigate(GameTree::forward)
+    }
+
+    pub fn previous_variation(&mut self) -> bool {
+        self.navigate(GameTree::previous_variation)
+    }
+
+    pub fn next_variation(&mut self) -> bool {
+        self.navigate(GameTree::next_variation)
+    }
+
+    pub fn promote_variation(&mut self) -> bool {
+        self.navigate(GameTree::promote_to_mainline)
+    }
+
+    pub fn delete_variation(&mut self) -> bool {
+        self.navigate(GameTree::delete_current)
+    }
+
+    fn navigate(&mut self, step: fn(&mut GameTree) -> bool) -> bool {
+        if !step(&mut self.tree) {
+            return false;
+        }
+        self.update_result();
+        true
+    }
+
+    pub fn result(&self) -> &Option<GameResult> {
+        &self.result
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.result.is_some()
+    }
+
+    // the result follows from the current position or was declared in it (e.g. a resignation),
+    // so taking back a move reopens the game and going forward again restores the result
+    fn update_result(&mut self) {
+        self.draw_offered = false;
+        self.result = match self.current_state().check_game_over() {
+            None if self.repetition_count() >= 5 => {
+                Some(GameResult::draw(Termination::FivefoldRepetition))
+            }
+            None => *self.tree.current().result(),
+            result => result,
+        };
+    }
+
+    // ends the game in the current position
+    fn declare_result(&mut self, result: GameResult) {
+        self.tree.set_result(Some(result));
+        self.result = Some(result);
+    }
+
+    // counts how often the current position occurred in the line leading to it (including itself).
+    // positions before the last capture or pawn move can't be repeated, so they are skipped.
+    pub fn repetition_count(&self) -> usize {
+        let current_state = self.current_state();
+        let identity = current_state.identity();
+        self.tree
+            .line()
+            .into_iter()
+            .rev()
+            .take(current_state.halfmove_clock as usize + 1)
+            .filter(|state| state.identity() == identity)
+            .count()
+    }
+
+    // a draw the player to move may claim, but which does not end the game by itself
+    pub fn claimable_draw(&self) -> Option<Termination> {
+        if self.is_over() {
+            return None;
+        }
+        if self.repetition_count() >= 3 {
+            return Some(Termination::ThreefoldRepetition);
+        }
+        if self.current_state().halfmove_clock() >= 100 {
+            return Some(Termination::FiftyMoveRule);
+        }
+        None
+    }
+
+    pub fn claim_draw(&mut self) -> bool {
+        match self.claimable_draw() {
+            Some(termination) => {
+                self.declare_result(GameResult::draw(termination));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn resign(&mut self, player: &Color) -> bool {
+        if self.is_over() {
+            return false;
+        }
+        let winner = match *player {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+            Color::None => panic!("Only a player can resign!"),
+        };
+        self.declare_result(GameResult::win(&winner, Termination::Resignation));
+        true
+    }
+
+    pub fn agree_draw(&mut self) -> bool {
+        if self.is_over() {
+            return false;
+        }
+        self.declare_result(GameResult::draw(Termination::Agreement));
+        true
+    }
+}
+
+// a game as it is read, before it is checked against its tree
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawGameState {
+    selected_field: Option<(usize, usize)>,
+    pending_promotion: Option<String>,
+    draw_offered: bool,
+    tree: GameTree,
+    result: Option<GameResult>,
+    tags: Vec<(String, String)>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawGameState> for GameState {
+    type Error = String;
+
+    fn try_from(raw: RawGameState) -> Result<GameState, String> {
+        // the result has to follow from the current position or be declared in it
+        let mut game = GameState::from_tree(raw.tree);
+        if game.result != raw.result {
+            return Err(format!(
+                "Invalid game, the result {:?} doesn't match the current position",
+                raw.result
+            ));
+        }
+        if raw.draw_offered && game.is_over() {
+            return Err(String::from("Invalid game, a draw is offered after the game ended"));
+        }
+        if let Some((rank, file)) = raw.selected_field {
+            if rank > 7 || file > 7 {
+                return Err(format!("Invalid game, the field ({}, {}) is selected", rank, file));
+            }
+        }
+
+        game.selected_field = raw.selected_field;
+        game.pending_promotion = raw.pending_promotion;
+        game.draw_offered = raw.draw_offered;
+        game.tags = raw.tags;
+        Ok(game)
+    }
 }
 
 pub fn handle_state(new_state: Result<State, MoveError>, game: &mut GameState) {
     use MoveError::*;
     match new_state {
-        Ok(new_state) => {
-            game.active_states.push_back(new_state);
-            game.inactive_states.clear();
-        },
+        Ok(_) if game.is_over() => println!("The game is already over!"),
+        Ok(new_state) => game.push_state(new_state),
         Err(OutOfBounds) => println!("Please stay within the bounds 1-8!"),
         Err(NoneDigitEntered) => println!("Please only enter digits!"),
-        Err(InvalidNumberOfDigits) => println!("Please enter four digits!"),
+        Err(InvalidNumberOfDigits) => println!("Please enter four digits (and optionally q, r, b or n)!"),
         Err(NoPieceSelected) => println!("No piece selected!"),
         Err(WrongColorSelected) => println!("Enemy piece selected!"),
         Err(PieceCantReachTarget) => println!("The selected piece can't reach this field!"),
@@ -110,15 +568,35 @@ pub fn handle_state(new_state: Result<State, MoveError>, game: &mut GameState) {
         Err(NotMovingOutOfCheck) => println!("You need to move out of check!"),
         Err(CastlingThroughCheck) => println!("You would be castling through check!"),
         Err(CastlingNotAvailable) => println!("You can't castle anymore!"),
+        Err(PromotionRequired) => println!("Please choose a piece to promote to (q, r, b or n)!"),
+        Err(InvalidPromotion) => println!("Pawns can only be promoted to a queen, rook, bishop or knight on the last rank!"),
+        Err(InvalidNotation) => println!("Please enter a move like e4, Nf3, exd5, O-O or e8=Q (or four digits)!"),
+        Err(AmbiguousMove) => println!("More than one piece can make this move, please add its file or rank (e.g. Nbd7)!"),
         Err(None) => println!("Invalid move!"),
     }
 }
 
-#[derive(Debug)]
+// the special moves (a normal move may still capture or promote)
+#[derive(PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MoveFlag {
+    Normal,
+    DoublePush,
+    EnPassant,
+    KingsideCastle,
+    QueensideCastle,
+}
+
+// moves are only serialized (see serialization.rs)
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Move {
     pub piece: Piece,
     pub start_field: Field,
     pub target_field: Field,
+    pub promotion: Option<PieceType>,
+    pub captured: Option<Piece>,
+    pub flag: MoveFlag,
 }
 
 impl Move {
@@ -134,6 +612,42 @@ impl Move {
         &self.target_field
     }
 
+    pub fn promotion(&self) -> &Option<PieceType> {
+        &self.promotion
+    }
+
+    // the enemy piece taken by the move (the pawn next to the target field for en passant)
+    pub fn captured(&self) -> &Option<Piece> {
+        &self.captured
+    }
+
+    pub fn flag(&self) -> &MoveFlag {
+        &self.flag
+    }
+
+    pub fn is_capture(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    pub fn is_castling(&self) -> bool {
+        self.flag == MoveFlag::KingsideCastle || self.flag == MoveFlag::QueensideCastle
+    }
+
+    // the field of the piece taken by the move
+    pub fn captured_field(&self) -> Option<Field> {
+        match (&self.captured, self.flag) {
+            (None, _) => None,
+            (Some(_), MoveFlag::EnPassant) => Some(Field(self.start_field.0, self.target_field.1)),
+            (Some(_), _) => Some(self.target_field.clone()),
+        }
+    }
+
+    // a pawn reaching the last rank has to be promoted
+    pub fn is_promoting(&self) -> bool {
+        self.piece.piecetype() == &PieceType::Pawn
+            && (self.target_field.0 == 0 || self.target_field.0 == 7)
+    }
+
     pub fn piece_string(&self) -> String {
         match self.piece.piecetype() {
             PieceType::Rook => String::from("Rook"),
@@ -146,6 +660,101 @@ impl Move {
         }
     }
 
+    // the move in standard algebraic notation (e.g. "Nbd7", "exd5", "e8=Q+" or "O-O-O#")
+    // the state is the one the move is played in (it needs to be legal there)
+    pub fn to_san(&self, state: &State) -> String {
+        let mut san = String::new();
+
+        if self.is_castling() {
+            match self.flag {
+                MoveFlag::KingsideCastle => san.push_str("O-O"),
+                _ => san.push_str("O-O-O"),
+            }
+        } else {
+            let is_capture = self.is_capture();
+
+            match Move::san_letter(self.piece.piecetype()) {
+                Some(letter) => {
+                    san.push(letter);
+                    san.push_str(&self.san_disambiguation(state));
+                }
+                // pawns are only named by the file they capture from
+                None if is_capture => san.push(Move::file_char(self.start_field.1)),
+                None => {}
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+            san.push(Move::file_char(self.target_field.1));
+            san.push(Move::rank_char(self.target_field.0));
+
+            if let Some(letter) = self.promotion.as_ref().and_then(Move::san_letter) {
+                san.push('=');
+                san.push(letter);
+            }
+        }
+
+        // the suffix tells whether the opponent is in check (or even checkmate) after the move
+        let mut new_state = state.clone();
+        new_state.make_move(self);
+        if new_state.is_player_in_check(new_state.turn()) {
+            match new_state.legal_moves().is_empty() {
+                true => san.push('#'),
+                false => san.push('+'),
+            }
+        }
+
+        san
+    }
+
+    // the start file, rank or both if other pieces of the same type could move to the
+    // target field as well
+    fn san_disambiguation(&self, state: &State) -> String {
+        let others: Vec<Field> = state
+            .legal_moves()
+            .into_iter()
+            .filter(|chess_move| {
+                chess_move.piece == self.piece
+                    && chess_move.target_field == self.target_field
+                    && chess_move.start_field != self.start_field
+                    && !chess_move.is_castling()
+            })
+            .map(|chess_move| chess_move.start_field)
+            .collect();
+
+        let file = Move::file_char(self.start_field.1).to_string();
+        let rank = Move::rank_char(self.start_field.0).to_string();
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|field| field.1 != self.start_field.1) {
+            file
+        } else if others.iter().all(|field| field.0 != self.start_field.0) {
+            rank
+        } else {
+            file + &rank
+        }
+    }
+
+    fn san_letter(piecetype: &PieceType) -> Option<char> {
+        match piecetype {
+            PieceType::King => Some('K'),
+            PieceType::Queen => Some('Q'),
+            PieceType::Rook => Some('R'),
+            PieceType::Bishop => Some('B'),
+            PieceType::Knight => Some('N'),
+            _ => None,
+        }
+    }
+
+    fn file_char(file: usize) -> char {
+        (b'a' + file as u8) as char
+    }
+
+    fn rank_char(rank: usize) -> char {
+        (b'1' + rank as u8) as char
+    }
+
     pub fn rank_difference(&self) -> isize {
         self.target_field.0 as isize - self.start_field.0 as isize
     }
@@ -166,33 +775,48 @@ impl Move {
         max(self.rank_distance(), self.file_distance())
     }
 
-    // for now we assume the syntax "AABB", where
+    // for now we assume the syntax "AABB[P]", where
     // AA are the indices of the source field (i.e., 52) [offset by 1]
     // BB are the indices of the target field (i.e., 54) [offset by 1]
-    // -> 2545 is equivalent to "e4" in standard notation.
-    fn new(
-        start_field: &Field,
-        target_field: &Field,
-        position_matrix: Ref<PositionMatrix>,
-    ) -> Move {
-        let piece = position_matrix.get_piece_on_field(start_field);
-        Move {
+    // P is the optional piece a pawn is promoted to (q, r, b or n)
+    // -> 2545 is equivalent to "e4" and 7178q to "a8=Q" in standard notation.
+    // the captured piece and the flag are taken from the state the move is made in
+    pub fn new(start_field: &Field, target_field: &Field, state: &State) -> Move {
+        let piece = state.board().get_piece_on_field(start_field);
+        let mut chess_move = Move {
             piece,
             start_field: start_field.clone(),
             target_field: target_field.clone(),
-        }
+            promotion: None,
+            captured: None,
+            flag: MoveFlag::Normal,
+        };
+        chess_move.flag = state.move_flag(&chess_move);
+        chess_move.captured = state.captured_piece(&chess_move);
+        chess_move
     }
 
     #[rustfmt::skip]
-    pub fn parse_move_input(player_input: String) -> Result<(Field, Field), MoveError> {
-        let move_chars: Vec<char> = player_input
+    pub fn parse_move_input(player_input: String) -> Result<(Field, Field, Option<PieceType>), MoveError> {
+        let mut move_chars: Vec<char> = player_input
             .chars()
             .filter(|c| *c != '\n' && *c != '\r')
             .collect();
 
-        if move_chars.len() != 4 {
+        if move_chars.len() != 4 && move_chars.len() != 5 {
             return Err(MoveError::InvalidNumberOfDigits);
         }
+
+        let promotion = match move_chars.len() {
+            5 => match move_chars.pop().unwrap().to_ascii_lowercase() {
+                'q' => Some(PieceType::Queen),
+                'r' => Some(PieceType::Rook),
+                'b' => Some(PieceType::Bishop),
+                'n' => Some(PieceType::Knight),
+                _ => return Err(MoveError::InvalidPromotion),
+            },
+            _ => None,
+        };
         
         for char in move_chars.iter() {
             if !char.is_ascii_digit() {
@@ -217,19 +841,28 @@ impl Move {
         let start_field = Field(move_indices[0] - 1, move_indices[1] - 1);
         let target_field = Field(move_indices[2] - 1, move_indices[3] - 1);
 
-        Ok((start_field, target_field))
+        Ok((start_field, target_field, promotion))
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(PartialEq, Clone, Debug)]
 pub struct Position(String);
 
+// everything that makes two positions 'the same' in terms of the repetition rules
+#[derive(PartialEq, Clone, Debug)]
+pub struct PositionIdentity {
+    board: Board,
+    turn: Color,
+    castle_availability: CastleAvailability,
+    en_passant: Option<Field>,
+}
+
 impl<'a> Position {
     pub fn split(&'a self) -> Vec<&'a str> {
         self.0.split('/').collect()
     }
 
-    pub fn update_from_matrix(&mut self, matrix: Ref<PositionMatrix>) {
+    pub fn update_from_matrix(&mut self, matrix: &PositionMatrix) {
         let mut new_position = String::new();
         let mut new_char: char = ' ';
         let numbers = vec!['1', '2', '3', '4', '5', '6', '7'];
@@ -271,54 +904,39 @@ impl<'a> Position {
     }
 }
 
+// the board as a matrix of pieces indexed by [rank][file], e.g. for drawing it
 #[derive(Clone, Debug)]
 pub struct PositionMatrix(pub Vec<Vec<Piece>>);
 
-impl PositionMatrix {
-    fn has_piece_on_field(&self, field: &Field) -> bool {
-        *self.0[field.0][field.1].piecetype() != PieceType::None
-    }
-
-    fn get_piece_on_field(&self, field: &Field) -> Piece {
-        self.0[field.0][field.1]
-    }
-
-    fn get_color_of_piece_on_field(&self, field: &Field) -> &Color {
-        self.0[field.0][field.1].color()
-    }
-
-    fn get_type_of_piece_on_field(&self, field: &Field) -> &PieceType {
-        self.0[field.0][field.1].piecetype()
-    }
-
-    fn remove_piece_from_field(&mut self, field: &Field) -> Piece {
-        let piece = self.0[field.0][field.1];
-        self.0[field.0][field.1] = Piece {
-            color: Color::None,
-            piecetype: PieceType::None,
-        };
-        piece
-    }
-
-    fn place_piece(&mut self, piece: Piece, field: &Field) -> Piece {
-        let current_piece: Piece = self.0[field.0][field.1];
-        self.0[field.0][field.1] = piece;
-        current_piece
-    }
+// everything that is needed to take back a move made by 'State::make_move'
+#[derive(PartialEq, Clone, Debug)]
+pub struct UndoInfo {
+    castling: Option<Castling>,
+    castle_availability: CastleAvailability,
+    en_passant: Option<Field>,
+    halfmove_clock: u16,
+    fullmove_clock: u16,
+    zobrist_key: u64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SerializedState", into = "SerializedState")
+)]
 pub struct State {
-    position: RefCell<Position>,
-    position_matrix: RefCell<PositionMatrix>,
+    board: Board,
     turn: Color,
     castle_availability: CastleAvailability,
     en_passant: Option<Field>,
     halfmove_clock: u16,
     fullmove_clock: u16,
+    chess960: bool,
+    zobrist_key: u64,
 }
 
-impl<'a> State {
+impl State {
     pub fn new(fen: Option<String>) -> State {
         let fen_start_string = match fen {
             Some(fen) => fen,
@@ -328,165 +946,327 @@ impl<'a> State {
     }
 
     pub fn load_game_from_fen(fen_string: String) -> State {
-        let game_state_vec: Vec<&str> = fen_string.split(' ').collect();
+        match State::from_fen(&fen_string) {
+            Ok(state) => state,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn from_fen(fen_string: &str) -> Result<State, FenError> {
+        let game_state_vec: Vec<&str> = fen_string.split_whitespace().collect();
+        if game_state_vec.len() != 6 {
+            return Err(FenError::WrongNumberOfFields(game_state_vec.len()));
+        }
+
+        let position = Position(String::from(game_state_vec[0]));
+        let board = match State::init_matrix(&position) {
+            Ok(matrix) => Board::from_matrix(&matrix),
+            Err(e) => return Err(e),
+        };
 
         let turn: Color = match game_state_vec[1] {
             "w" => Color::White,
             "b" => Color::Black,
-            _ => panic!("Invalid FEN string, turn value: {}", game_state_vec[1]),
+            _ => return Err(FenError::InvalidTurn(String::from(game_state_vec[1]))),
         };
 
-        let castle_availability: CastleAvailability = match game_state_vec[2] {
-            "KQkq" => CastleAvailability {
-                white_king: true,
-                white_queen: true,
-                black_king: true,
-                black_queen: true,
-            },
-            "KQk" => CastleAvailability {
-                white_king: true,
-                white_queen: true,
-                black_king: true,
-                black_queen: false,
-            },
-            "KQq" => CastleAvailability {
-                white_king: true,
-                white_queen: true,
-                black_king: false,
-                black_queen: true,
-            },
-            "KQ" => CastleAvailability {
-                white_king: true,
-                white_queen: true,
-                black_king: false,
-                black_queen: false,
-            },
-            "Kkq" => CastleAvailability {
-                white_king: true,
-                white_queen: false,
-                black_king: true,
-                black_queen: true,
-            },
-            "Kk" => CastleAvailability {
-                white_king: true,
-                white_queen: false,
-                black_king: true,
-                black_queen: false,
-            },
-            "Kq" => CastleAvailability {
-                white_king: true,
-                white_queen: false,
-                black_king: false,
-                black_queen: true,
-            },
-            "K" => CastleAvailability {
-                white_king: true,
-                white_queen: false,
-                black_king: false,
-                black_queen: false,
-            },
-            "Qkq" => CastleAvailability {
-                white_king: false,
-                white_queen: true,
-                black_king: true,
-                black_queen: true,
-            },
-            "Qk" => CastleAvailability {
-                white_king: false,
-                white_queen: true,
-                black_king: true,
-                black_queen: false,
-            },
-            "Qq" => CastleAvailability {
-                white_king: false,
-                white_queen: true,
-                black_king: false,
-                black_queen: true,
-            },
-            "Q" => CastleAvailability {
-                white_king: false,
-                white_queen: true,
-                black_king: false,
-                black_queen: false,
-            },
-            "kq" => CastleAvailability {
-                white_king: false,
-                white_queen: false,
-                black_king: true,
-                black_queen: true,
-            },
-            "k" => CastleAvailability {
-                white_king: false,
-                white_queen: false,
-                black_king: true,
-                black_queen: false,
-            },
-            "q" => CastleAvailability {
-                white_king: false,
-                white_queen: false,
-                black_king: false,
-                black_queen: true,
-            },
-            "-" => CastleAvailability {
-                white_king: false,
-                white_queen: false,
-                black_king: false,
-                black_queen: false,
+        let castle_availability = match State::parse_castle_availability(game_state_vec[2], &board) {
+            Some(castle_availability) => castle_availability,
+            None => return Err(FenError::InvalidCastling(String::from(game_state_vec[2]))),
+        };
+
+        // castling with file letters (Shredder-FEN) or rooks and king away from their
+        // standard fields can only be Chess960
+        let chess960 = game_state_vec[2].chars().any(|c| !"KQkq-".contains(c))
+            || State::has_chess960_castling(&castle_availability, &board);
+
+        let en_passant: Option<Field> = match game_state_vec[3] {
+            "-" => None,
+            value => match State::parse_en_passant(value) {
+                Some(field) => Some(field),
+                None => return Err(FenError::InvalidEnPassant(String::from(value))),
             },
-            _ => panic!(
-                "Invalid FEN string, castle availability value: {}",
-                game_state_vec[2]
-            ),
         };
 
-        let mut en_passant: Option<Field> = None;
-        if game_state_vec[3].len() > 2 {
-            panic!(
-                "Invalid FEN string, en-passant value: {}",
-                game_state_vec[3]
-            )
-        } else if game_state_vec[3] != "-" {
-            let mut field: Field = Field(9, 9);
-            let en_passant_chars: Vec<char> = game_state_vec[3].chars().collect();
-            match en_passant_chars[0] {
-                'a' => field.1 = 0,
-                'b' => field.1 = 1,
-                'c' => field.1 = 2,
-                'd' => field.1 = 3,
-                'e' => field.1 = 4,
-                'f' => field.1 = 5,
-                'g' => field.1 = 6,
-                'h' => field.1 = 7,
-                _ => panic!(
-                    "Invalid FEN string, en-passant value: {}",
-                    game_state_vec[3]
-                ),
-            }
-            field.0 = (en_passant_chars[1].to_digit(10).unwrap() - 1) as usize;
-
-            en_passant = Some(field);
-        }
-
-        let halfmove_clock: u16 = game_state_vec[4].parse::<u16>().unwrap();
-        let fullmove_clock: u16 = game_state_vec[5].parse::<u16>().unwrap();
-
-        State {
-            position: RefCell::new(Position(String::from(game_state_vec[0]))),
-            position_matrix: State::init_matrix(&Position(String::from(game_state_vec[0]))),
+        let halfmove_clock: u16 = match game_state_vec[4].parse::<u16>() {
+            Ok(clock) => clock,
+            Err(_) => return Err(FenError::InvalidHalfmoveClock(String::from(game_state_vec[4]))),
+        };
+        // the moves are counted from 1
+        let fullmove_clock: u16 = match game_state_vec[5].parse::<u16>() {
+            Ok(clock) if clock > 0 => clock,
+            _ => return Err(FenError::InvalidFullmoveClock(String::from(game_state_vec[5]))),
+        };
+
+        let mut state = State {
+            board,
             turn,
             castle_availability,
             en_passant,
             halfmove_clock,
             fullmove_clock,
+            chess960,
+            zobrist_key: 0,
+        };
+        state.zobrist_key = zobrist::key(&state);
+        match state.validate() {
+            Ok(_) => Ok(state),
+            Err(e) => Err(FenError::InvalidPosition(e)),
+        }
+    }
+
+    /*
+        Rejects setups that can't occur in a game: each side needs exactly one king, pawns can't
+        stand on the first or last rank and the player who just moved can't be in check. Castling
+        requires king and rook on their home rank (with the rook on the side of the king it castles
+        to), while en passant requires the pawn that just moved two fields (with the fields it
+        passed empty).
+    */
+    pub fn validate(&self) -> Result<(), PositionError> {
+        for color in [Color::White, Color::Black] {
+            let kings = bitboard::fields(self.board.pieces(&color, &PieceType::King)).count();
+            if kings != 1 {
+                return Err(PositionError::WrongNumberOfKings(color, kings));
+            }
+        }
+
+        let pawns = self.board.pieces_of_type(&PieceType::Pawn);
+        if let Some(field) = bitboard::fields(pawns & bitboard::BACK_RANKS).next() {
+            return Err(PositionError::PawnOnBackRank(field));
+        }
+
+        if self.is_player_in_check(self.turn_rev()) {
+            return Err(PositionError::OpponentInCheck);
+        }
+
+        for color in [Color::White, Color::Black] {
+            let home_rank = match color {
+                Color::White => 0,
+                _ => 7,
+            };
+            let king_file = match self.board.king_field(&color) {
+                Some(field) if field.0 == home_rank => Some(field.1),
+                _ => None,
+            };
+            let rook = Piece::new(color, PieceType::Rook);
+            for kingside in [true, false] {
+                let rook_file = match self.castle_availability.rook_file(&color, kingside) {
+                    Some(file) => file,
+                    None => continue,
+                };
+                let possible = match king_file {
+                    Some(king_file) => {
+                        (rook_file > king_file) == kingside
+                            && self.board.get_piece_on_field(&Field(home_rank, rook_file)) == rook
+                    }
+                    None => false,
+                };
+                if !possible {
+                    return Err(PositionError::ImpossibleCastling(color, rook_file));
+                }
+            }
+        }
+
+        if let Some(field) = &self.en_passant {
+            // the ranks of the en passant field, the pawn and the field it came from
+            let (rank, pawn_rank, start_rank) = match self.turn {
+                Color::White => (5, 4, 6),
+                _ => (2, 3, 1),
+            };
+            let pawn = Piece::new(*self.turn_rev(), PieceType::Pawn);
+            if field.0 != rank
+                || self.board.get_piece_on_field(&Field(pawn_rank, field.1)) != pawn
+                || self.board.has_piece_on_field(field)
+                || self.board.has_piece_on_field(&Field(start_rank, field.1))
+            {
+                return Err(PositionError::ImpossibleEnPassant(field.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    // any combination of 'K', 'Q', 'k' and 'q' (each at most once) or '-'
+    // in X-FEN 'K' / 'Q' refer to the outermost rook, other rooks are given by their file
+    // (e.g. 'G')
+    // Shredder-FEN always uses the files (e.g. 'HAha')
+    fn parse_castle_availability(value: &str, board: &Board) -> Option<CastleAvailability> {
+        let mut castle_availability = CastleAvailability::none();
+        if value == "-" {
+            return Some(castle_availability);
+        }
+        if value.is_empty() {
+            return None;
+        }
+
+        for char in value.chars() {
+            let color = match char.is_ascii_uppercase() {
+                true => Color::White,
+                false => Color::Black,
+            };
+            let king_file = State::home_king_file(board, &color);
+
+            let (kingside, rook_file) = match char.to_ascii_lowercase() {
+                'k' => (true, State::outermost_rook_file(board, &color, true).unwrap_or(7)),
+                'q' => (false, State::outermost_rook_file(board, &color, false).unwrap_or(0)),
+                'a'..='h' => {
+                    let file = char.to_ascii_lowercase() as usize - 'a' as usize;
+                    (file > king_file, file)
+                }
+                _ => return None,
+            };
+
+            let availability = castle_availability.rook_file_mut(&color, kingside).unwrap();
+            if availability.is_some() {
+                return None; // each right may only be listed once
+            }
+            *availability = Some(rook_file);
+        }
+        Some(castle_availability)
+    }
+
+    // the file of the king on its first rank (the e-file if it isn't there)
+    fn home_king_file(board: &Board, color: &Color) -> usize {
+        let home_rank = match color {
+            Color::White => 0,
+            _ => 7,
+        };
+        match board.king_field(color) {
+            Some(field) if field.0 == home_rank => field.1,
+            _ => 4,
+        }
+    }
+
+    fn outermost_rook_file(board: &Board, color: &Color, kingside: bool) -> Option<usize> {
+        let home_rank = match color {
+            Color::White => 0,
+            _ => 7,
+        };
+        let king_file = State::home_king_file(board, color);
+        let rook = Piece::new(*color, PieceType::Rook);
+        let files: Vec<usize> = match kingside {
+            true => (king_file + 1..8).rev().collect(),
+            false => (0..king_file).collect(),
+        };
+        files
+            .into_iter()
+            .find(|file| board.get_piece_on_field(&Field(home_rank, *file)) == rook)
+    }
+
+    fn has_chess960_castling(castle_availability: &CastleAvailability, board: &Board) -> bool {
+        for color in [Color::White, Color::Black] {
+            for (kingside, corner) in [(true, 7), (false, 0)] {
+                match castle_availability.rook_file(&color, kingside) {
+                    Some(file) if file != corner || State::home_king_file(board, &color) != 4 => {
+                        return true
+                    }
+                    _ => {}
+                }
+            }
+        }
+        false
+    }
+
+    // the en passant field is always on the third or sixth rank
+    fn parse_en_passant(value: &str) -> Option<Field> {
+        let en_passant_chars: Vec<char> = value.chars().collect();
+        if en_passant_chars.len() != 2 {
+            return None;
         }
+
+        let file = match en_passant_chars[0] {
+            'a'..='h' => en_passant_chars[0] as usize - 'a' as usize,
+            _ => return None,
+        };
+        let rank = match en_passant_chars[1] {
+            '3' => 2,
+            '6' => 5,
+            _ => return None,
+        };
+        Some(Field(rank, file))
+    }
+
+    // the inverse of 'from_fen', e.g. "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+    // castling rights of Chess960 positions are written as in X-FEN
+    pub fn to_fen(&self) -> String {
+        self.fen(false)
+    }
+
+    // like 'to_fen', but the castling rights are given by the rook files (e.g. "HAha")
+    pub fn to_shredder_fen(&self) -> String {
+        self.fen(true)
+    }
+
+    fn fen(&self, shredder: bool) -> String {
+        let turn = match self.turn {
+            Color::White => "w",
+            _ => "b",
+        };
+
+        let mut castle_availability = String::new();
+        for color in [Color::White, Color::Black] {
+            for kingside in [true, false] {
+                let rook_file = match self.castle_availability.rook_file(&color, kingside) {
+                    Some(file) => file,
+                    None => continue,
+                };
+                let char = if !shredder
+                    && State::outermost_rook_file(&self.board, &color, kingside) == Some(rook_file)
+                {
+                    match kingside {
+                        true => 'k',
+                        false => 'q',
+                    }
+                } else {
+                    (b'a' + rook_file as u8) as char
+                };
+                castle_availability.push(match color {
+                    Color::White => char.to_ascii_uppercase(),
+                    _ => char,
+                });
+            }
+        }
+        if castle_availability.is_empty() {
+            castle_availability.push('-');
+        }
+
+        let en_passant = match &self.en_passant {
+            Some(field) => format!("{}{}", (b'a' + field.1 as u8) as char, field.0 + 1),
+            None => String::from("-"),
+        };
+
+        format!(
+            "{} {} {} {} {} {}",
+            self.position().0,
+            turn,
+            castle_availability,
+            en_passant,
+            self.halfmove_clock,
+            self.fullmove_clock
+        )
+    }
+
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    // standard start positions are valid Chess960 positions as well,
+    // so the mode can't always be told from the FEN
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
     }
 
-    pub fn position(&'a self) -> &RefCell<Position> {
-        &self.position
+    pub fn position(&self) -> Position {
+        let mut position = Position(String::new());
+        position.update_from_matrix(&self.position_matrix());
+        position
     }
 
-    pub fn position_matrix(&self) -> &RefCell<PositionMatrix> {
-        &self.position_matrix
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn position_matrix(&self) -> PositionMatrix {
+        self.board.to_matrix()
     }
 
     pub fn castle_availability(&self) -> &CastleAvailability {
@@ -497,9 +1277,62 @@ impl<'a> State {
         &self.en_passant
     }
 
-    fn init_matrix(start_position: &Position) -> RefCell<PositionMatrix> {
+    // the Polyglot style Zobrist key of the position, see 'zobrist.rs'
+    pub fn zobrist_key(&self) -> u64 {
+        self.zobrist_key
+    }
+
+    pub fn identity(&self) -> PositionIdentity {
+        // the en passant field only matters if the capture can actually be made
+        let en_passant = match self.en_passant_capture_possible() {
+            true => self.en_passant.clone(),
+            false => None,
+        };
+
+        PositionIdentity {
+            board: self.board,
+            turn: self.turn,
+            castle_availability: self.castle_availability.clone(),
+            en_passant,
+        }
+    }
+
+    fn en_passant_capture_possible(&self) -> bool {
+        let target = match self.en_passant() {
+            Some(field) => field,
+            None => return false,
+        };
+
+        let start_rank = match *self.turn() {
+            Color::White => target.0 - 1,
+            Color::Black => target.0 + 1,
+            Color::None => return false,
+        };
+
+        for start_file in [target.1 as isize - 1, target.1 as isize + 1] {
+            if !(0..8).contains(&start_file) {
+                continue;
+            }
+            let chess_move = Move::new(
+                &Field(start_rank, start_file as usize),
+                target,
+                self,
+            );
+            if chess_move.piece().piecetype() == &PieceType::Pawn
+                && self.is_move_legal(&chess_move).is_ok()
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn init_matrix(start_position: &Position) -> Result<PositionMatrix, FenError> {
         let mut matrix: Vec<Vec<Piece>> = Vec::new();
         let ranks: Vec<&str> = start_position.split();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongNumberOfRanks(ranks.len()));
+        }
         for (i, rank) in ranks.iter().rev().enumerate() {
             matrix.push(Vec::new());
             for char in rank.chars() {
@@ -555,17 +1388,14 @@ impl<'a> State {
                         color: Color::White,
                         piecetype: PieceType::Pawn,
                     }),
-                    _ => panic!("Position is corrupt\n{:?}", rank.chars()),
+                    _ => return Err(FenError::UnknownPiece(char)),
                 };
             }
-            assert!(
-                matrix[i].len() == 8,
-                "Position matrix doesn't have 8 files\n{:?}",
-                matrix[i]
-            );
+            if matrix[i].len() != 8 {
+                return Err(FenError::BadRankLength(i + 1));
+            }
         }
-        assert!(matrix.len() == 8, "Position matrix doesn't have 8 ranks");
-        RefCell::new(PositionMatrix(matrix))
+        Ok(PositionMatrix(matrix))
     }
 
     fn push_none(rank: &mut Vec<Piece>, num: usize) {
@@ -585,6 +1415,14 @@ impl<'a> State {
         &self.turn
     }
 
+    pub fn halfmove_clock(&self) -> u16 {
+        self.halfmove_clock
+    }
+
+    pub fn fullmove_clock(&self) -> u16 {
+        self.fullmove_clock
+    }
+
     pub fn turn_rev(&self) -> &Color {
         match self.turn {
             Color::White => &Color::Black,
@@ -596,81 +1434,183 @@ impl<'a> State {
     fn player_has_legal_move(&self) -> bool {
         // iterate through the whole field and try to move each of the players piece
         // if one piece can move, return true
-        for (i, rank) in self.position_matrix().borrow().0.iter().enumerate() {
-            for (j, piece) in rank.iter().enumerate() {
-                // ignore empty fields and enemy pieces
-                if piece.color() != self.turn() {
-                    continue;
+        for rank in 0..8 {
+            for file in 0..8 {
+                if !self.legal_moves_from(&Field(rank, file)).is_empty() {
+                    return true;
                 }
+            }
+        }
 
-                // check each target field for legal moves
-                for (k, rank) in self.position_matrix().borrow().0.iter().enumerate() {
-                    for (l, piece) in rank.iter().enumerate() {
-                        // if the player already has a piece on this field
-                        // we can discount it
-                        if piece.color() == self.turn() {
-                            continue;
-                        }
+        // if no legal moves were found, return false
+        false
+    }
+
+    // all legal moves of the player to move, including castling, en passant and
+    // one move for each piece a pawn can be promoted to
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for rank in 0..8 {
+            for file in 0..8 {
+                moves.append(&mut self.legal_moves_from(&Field(rank, file)));
+            }
+        }
+        moves
+    }
 
-                        let chess_move =
-                            Move::new(&Field(i, j), &Field(k, l), self.position_matrix().borrow());
+    pub fn legal_moves_from(&self, field: &Field) -> Vec<Move> {
+        let piece = self.board().get_piece_on_field(field);
+        if piece.color() != self.turn() {
+            return Vec::new();
+        }
 
-                        match self.is_move_legal(&chess_move) {
-                            Ok(_) => return true,
-                            Err(_) => continue,
-                        }
+        let mut moves = Vec::new();
+        let mut scratch = self.clone();
+        for target_field in self.candidate_target_fields(&piece, field) {
+            let mut chess_move = Move::new(field, &target_field, self);
+            match self.is_move_legal_with(&chess_move, &mut scratch) {
+                Ok(_) => moves.push(chess_move),
+                Err(MoveError::PromotionRequired) => {
+                    for piecetype in [
+                        PieceType::Queen,
+                        PieceType::Rook,
+                        PieceType::Bishop,
+                        PieceType::Knight,
+                    ] {
+                        chess_move.promotion = Some(piecetype);
+                        moves.push(chess_move.clone());
                     }
                 }
+                Err(_) => {}
             }
         }
+        moves
+    }
 
-        // if no legal moves were found, return false
-        false
+    // the fields a piece could move to (castling included),
+    // whether it actually can is decided by 'is_move_legal'
+    fn candidate_target_fields(&self, piece: &Piece, field: &Field) -> Vec<Field> {
+        let targets = match piece.piecetype() {
+            PieceType::Pawn => {
+                let push = match piece.color() {
+                    Color::White if field.0 < 7 => bitboard::bit(&Field(field.0 + 1, field.1)),
+                    Color::Black if field.0 > 0 => bitboard::bit(&Field(field.0 - 1, field.1)),
+                    _ => bitboard::EMPTY,
+                };
+                let double_push = match piece.color() {
+                    Color::White if field.0 == 1 => bitboard::bit(&Field(3, field.1)),
+                    Color::Black if field.0 == 6 => bitboard::bit(&Field(4, field.1)),
+                    _ => bitboard::EMPTY,
+                };
+                self.board().attacks(piece, field) | push | double_push
+            }
+            _ => self.board().attacks(piece, field),
+        };
+        let mut targets = targets & !self.board().pieces_of_color(piece.color());
+
+        if piece.piecetype() == &PieceType::King {
+            if self.chess960 {
+                // in Chess960 the king castles by moving onto its own rook
+                for kingside in [true, false] {
+                    if let Some(file) = self.castle_availability.rook_file(piece.color(), kingside) {
+                        targets |= bitboard::bit(&Field(field.0, file));
+                    }
+                }
+            } else {
+                if field.1 >= 2 {
+                    targets |= bitboard::bit(&Field(field.0, field.1 - 2));
+                }
+                if field.1 <= 5 {
+                    targets |= bitboard::bit(&Field(field.0, field.1 + 2));
+                }
+            }
+        }
+
+        bitboard::fields(targets).collect()
     }
 
-    pub fn check_game_over(&self) -> GameOver {
+    // the game results that follow from the position alone
+    pub fn check_game_over(&self) -> Option<GameResult> {
         if self.player_has_legal_move() {
-            return GameOver::No;
+            // after 75 moves by each player without a capture or pawn move the game is drawn
+            // (unless the last move checkmated, which is covered below)
+            if self.halfmove_clock() >= 150 {
+                return Some(GameResult::draw(Termination::SeventyFiveMoveRule));
+            }
+            if self.board().has_insufficient_material() {
+                return Some(GameResult::draw(Termination::InsufficientMaterial));
+            }
+            return None;
         }
 
         if !self.is_player_in_check(&Color::None) {
-            return GameOver::Stalemate;
+            return Some(GameResult::draw(Termination::Stalemate));
         }
 
-        if self.turn() == &Color::White {
-            GameOver::BlackWon
-        } else {
-            GameOver::WhiteWon
-        }
+        Some(GameResult::win(self.turn_rev(), Termination::Checkmate))
     }
 
+    // the input is either given in the digit notation (see 'parse_move_input'),
+    // as a UCI move (e.g. "g1f3") or in SAN (e.g. "Nf3")
     pub fn perform_turn_from_input(
         player_input: String,
         current_state: &State,
     ) -> Result<State, MoveError> {
+        match State::move_from_input(player_input, current_state) {
+            Ok(chess_move) => Ok(current_state.execute_move(&chess_move)),
+            Err(e) => Err(e),
+        }
+    }
+
+    // the legal move given by the input (in SAN, UCI or digit notation)
+    pub fn move_from_input(
+        player_input: String,
+        current_state: &State,
+    ) -> Result<Move, MoveError> {
+
+        if State::is_uci(&player_input) {
+            return current_state.parse_uci(&player_input);
+        }
+
+        // digits are fields, unless they are zeros for castling (e.g. "0-0")
+        let input = player_input.trim();
+        if !input.starts_with(|c: char| c.is_ascii_digit()) || input.starts_with("0-0") {
+            return current_state.parse_san(&player_input);
+        }
 
         // get fields from player input
-        let (start_field, target_field) = match Move::parse_move_input(player_input) {
-            Ok((start_field, target_field)) => (start_field, target_field),
+        let (start_field, target_field, promotion) = match Move::parse_move_input(player_input) {
+            Ok((start_field, target_field, promotion)) => (start_field, target_field, promotion),
             Err(e) => return Err(e),
         };
 
         // make move struct which also includes the piece that is to be moved
-        let chess_move = Move::new(
+        let mut chess_move = Move::new(
             &start_field,
             &target_field,
-            current_state.position_matrix().borrow(),
+            current_state,
         );
+        chess_move.promotion = promotion;
 
         // check whether the move is legal
         // TO DO: errors should properly propagated to the UI rather than panicking!
         match current_state.is_move_legal(&chess_move) {            
-            Ok(_) => Ok(current_state.execute_move(&chess_move)),
+            Ok(_) => Ok(chess_move),
             Err(e) => Err(e),
         }
     }
 
-    fn is_move_legal(&self, chess_move: &Move) -> Result<bool, MoveError> {
+    pub fn is_move_legal(&self, chess_move: &Move) -> Result<bool, MoveError> {
+        self.is_move_legal_with(chess_move, &mut self.clone())
+    }
+
+    // 'scratch' is a copy of this state the move is made and taken back on, so checking
+    // several moves only needs a single copy
+    fn is_move_legal_with(
+        &self,
+        chess_move: &Move,
+        scratch: &mut State,
+    ) -> Result<bool, MoveError> {
 
         // assert that a piece was selected
         if chess_move.piece().piecetype() == &PieceType::None
@@ -692,11 +1632,12 @@ impl<'a> State {
         }
 
         // assert that none of the player's own pieces are on the target field
-        if self
-            .position_matrix()
-            .borrow()
+        // (except for the rook when castling by moving the king onto it)
+        if &self
+            .board()
             .get_color_of_piece_on_field(chess_move.target_field())
             == self.turn()
+            && self.castling(chess_move).is_none()
         {
             return Err(MoveError::OwnPieceOnTarget);
         }
@@ -707,8 +1648,10 @@ impl<'a> State {
         }
 
         // make a 'hypothetical move' and check whether the player would be in check
-        let hypothetical_state: State = self.execute_move(chess_move);
-        if hypothetical_state.is_player_in_check(self.turn()) {
+        let undo_info = scratch.make_move(chess_move);
+        let in_check = scratch.is_player_in_check(self.turn());
+        scratch.unmake_move(chess_move, undo_info);
+        if in_check {
             if chess_move.piece.piecetype() == &PieceType::King {
                 // 1) king would have moved into check
                 return Err(MoveError::MovingIntoCheck);
@@ -728,10 +1671,30 @@ impl<'a> State {
             return Err(MoveError::CastlingThroughCheck);
         }
 
+        // assert that a pawn reaching the last rank is promoted to a proper piece
+        // this is checked last, so that 'PromotionRequired' implies an otherwise legal move
+        match State::promotion_result(chess_move) {
+            Ok(_) => {},
+            Err(e) => return Err(e),
+        }
+
         // the move is legal, if no condition made it illegal
         Ok(true)
     }
 
+    fn promotion_result(chess_move: &Move) -> Result<bool, MoveError> {
+        match chess_move.promotion() {
+            None if chess_move.is_promoting() => Err(MoveError::PromotionRequired),
+            None => Ok(true),
+            Some(_) if !chess_move.is_promoting() => Err(MoveError::InvalidPromotion),
+            Some(PieceType::Queen)
+            | Some(PieceType::Rook)
+            | Some(PieceType::Bishop)
+            | Some(PieceType::Knight) => Ok(true),
+            Some(_) => Err(MoveError::InvalidPromotion),
+        }
+    }
+
     fn is_players_turn(&self, turn: &Color) -> bool {
         return self.turn() == turn;
     }
@@ -743,7 +1706,7 @@ impl<'a> State {
         let rank_diff_abs = isize::abs(rank_diff);
         let file_diff_abs = isize::abs(file_diff);
         let color = chess_move.piece().color();
-        let matrix = self.position_matrix().borrow();
+        let board = self.board();
 
         match chess_move.piece().piecetype() {
             PieceType::Rook => State::can_reach_target_result(
@@ -758,20 +1721,11 @@ impl<'a> State {
                 ||  ((rank_diff == 0) ^ (file_diff == 0))
             ),
             PieceType::King => {
-                if rank_diff_abs <= 1 && file_diff_abs <= 1 && (rank_diff_abs + file_diff_abs) >= 1 {
-                    Ok(true)
-                } else {
-                    let is_castling = match *color {
-                        Color::White => (
-                            chess_move.target_field().0 == 0 && chess_move.target_field().1 == 2, 
-                            chess_move.target_field().0 == 0 && chess_move.target_field().1 == 6), 
-                        Color::Black => (
-                            chess_move.target_field().0 == 7 && chess_move.target_field().1 == 2, 
-                            chess_move.target_field().0 == 7 && chess_move.target_field().1 == 6),
-                        _ => panic!("Invalid color"),
-                    };
-                    State::castle_availability_result(color, is_castling, self.castle_availability())
+                if let Some(castling) = self.castling(chess_move) {
+                    return self.castling_result(color, &castling);
                 }
+                State::can_reach_target_result(
+                    rank_diff_abs <= 1 && file_diff_abs <= 1 && (rank_diff_abs + file_diff_abs) >= 1)
             },
             PieceType::Pawn => {
                 if  (file_diff == 0 && rank_diff ==  1 && color == &Color::White)
@@ -791,8 +1745,8 @@ impl<'a> State {
                 match self.en_passant() {
                     Some(field) if field == &chess_move.target_field => Ok(true),
                     _ => State::can_reach_target_result(
-                        matrix.get_type_of_piece_on_field(chess_move.target_field()) != &PieceType::None
-                     && matrix.get_color_of_piece_on_field(chess_move.target_field()) == &opposite_color)
+                        board.get_type_of_piece_on_field(chess_move.target_field()) != PieceType::None
+                     && board.get_color_of_piece_on_field(chess_move.target_field()) == opposite_color)
                 }
             }
             _ => panic!("Move not properly processed. {:?}", chess_move),
@@ -807,277 +1761,299 @@ impl<'a> State {
         }
     }
     
-    fn castle_availability_result(color: &Color, is_castling: (bool, bool), castle_available: &CastleAvailability) -> Result<bool, MoveError> {
-        let availability = match *color {
-            Color::White => (castle_available.white_queen, castle_available.white_king),
-            Color::Black => (castle_available.black_queen, castle_available.black_king),
-            _ => panic!("Invalid color"), 
-        };
-
-        if is_castling.0 || is_castling.1 {
-            if is_castling.0 && availability.0 || is_castling.1 && availability.1 {
-                Ok(true)
-            } else {
-                Err(MoveError::CastlingNotAvailable)
-            } 
-        } else {
-            Err(MoveError::PieceCantReachTarget)
+    fn castling_result(&self, color: &Color, castling: &Castling) -> Result<bool, MoveError> {
+        // the player needs the right to castle with a rook that is still on its field
+        let kingside = castling.king_target.1 == 6;
+        let rook = Piece::new(*color, PieceType::Rook);
+        if self.castle_availability.rook_file(color, kingside) != Some(castling.rook_start.1)
+            || self.board().get_piece_on_field(&castling.rook_start) != rook
+        {
+            return Err(MoveError::CastlingNotAvailable);
         }
+        Ok(true)
     }
 
     fn piece_has_path_to_target_field(&self, chess_move: &Move) -> bool {
-        match chess_move.piece().piecetype() {
-            PieceType::Pawn | PieceType::King | PieceType::Knight => true,
+        let path = match chess_move.piece().piecetype() {
             PieceType::Rook | PieceType::Bishop | PieceType::Queen => {
-                let rank_diff: isize =
-                    chess_move.target_field().0 as isize - chess_move.start_field().0 as isize;
-                let file_diff: isize =
-                    chess_move.target_field().1 as isize - chess_move.start_field().1 as isize;
-                let rank_diff_abs = isize::abs(rank_diff);
-                let file_diff_abs = isize::abs(file_diff);
-
-                let rank_direction = match rank_diff {
-                    0 => 0,
-                    _ => rank_diff / rank_diff_abs,
-                };
-
-                let file_direction = match file_diff {
-                    0 => 0,
-                    _ => file_diff / file_diff_abs,
-                };
-
-                for i in 1..max(rank_diff_abs, file_diff_abs) {
-                    let rank_index =
-                        (chess_move.start_field().0 as isize + i * rank_direction) as usize;
-                    let file_index =
-                        (chess_move.start_field().1 as isize + i * file_direction) as usize;
-                    if self
-                        .position_matrix()
-                        .borrow()
-                        .has_piece_on_field(&Field(rank_index, file_index))
-                    {
-                        return false;
-                    }
-                }
-                true
+                bitboard::between(chess_move.start_field(), chess_move.target_field())
             }
-            _ => true,
-        }
+            // pawns moving straight ahead can neither jump nor capture
+            PieceType::Pawn if chess_move.file_distance() == 0 => {
+                bitboard::between(chess_move.start_field(), chess_move.target_field())
+                    | bitboard::bit(chess_move.target_field())
+            }
+            // all fields king and rook pass or move to need to be empty when castling
+            // (apart from the fields of king and rook themselves)
+            PieceType::King => match self.castling(chess_move) {
+                Some(castling) => {
+                    let files = [
+                        chess_move.start_field().1,
+                        castling.king_target.1,
+                        castling.rook_start.1,
+                        castling.rook_target.1,
+                    ];
+                    let rank = chess_move.start_field().0;
+                    let first = Field(rank, *files.iter().min().unwrap());
+                    let last = Field(rank, *files.iter().max().unwrap());
+                    (bitboard::between(&first, &last) | bitboard::bit(&first) | bitboard::bit(&last))
+                        & !bitboard::bit(chess_move.start_field())
+                        & !bitboard::bit(&castling.rook_start)
+                }
+                None => bitboard::EMPTY,
+            },
+            _ => bitboard::EMPTY,
+        };
+        path & self.board().occupied() == bitboard::EMPTY
     }
 
     fn is_castling_through_check(&self, chess_move: &Move) -> bool {
-        if chess_move.piece().piecetype() != &PieceType::King {
-            return false;
-        }
-
-        let file_diff = chess_move.file_difference();
-        let file_diff_abs = isize::abs(file_diff);
-        if file_diff_abs < 2 {
-            return false;
+        let castling = match self.castling(chess_move) {
+            Some(castling) => castling,
+            None => return false,
         };
 
-        // for all fields in the kings path
+        // for all fields in the kings path (including the start and target field)
         // check whether an enemy piece is attacking it
-        // note that the target field is _not_ checked!
         let enemy_color: Color = match *self.turn() {
             Color::White => Color::Black,
             Color::Black => Color::White,
             _ => panic!("No valid player color requested!"),
         };
-        let direction = file_diff / file_diff_abs;
-        for i in 0..(file_diff_abs + 1) {
-            let field: Field = Field(
-                chess_move.start_field().0,
-                (chess_move.start_field().1 as isize + direction * i) as usize,
-            );
-            if self.is_players_piece_attacking_field(&enemy_color, &field) {
-                return true;
-            }
-        }
-        false
+        let king_path = bitboard::between(chess_move.start_field(), &castling.king_target)
+            | bitboard::bit(chess_move.start_field())
+            | bitboard::bit(&castling.king_target);
+        bitboard::fields(king_path).any(|field| self.is_players_piece_attacking_field(&enemy_color, &field))
     }
 
     fn is_players_piece_attacking_field(&self, player: &Color, field: &Field) -> bool {
-        for (i, rank) in self.position_matrix().borrow().0.iter().enumerate() {
-            for (j, piece) in rank.iter().enumerate() {
-                if piece.color() != player {
-                    continue;
-                }
+        self.board().is_field_attacked(field, player)
+    }
 
-                let chess_move = Move::new(&Field(i, j), field, self.position_matrix().borrow());
-                match State::piece_can_reach_target_field(self, &chess_move) {
-                    Ok(_) if State::piece_has_path_to_target_field(self, &chess_move) => return true,
-                    _ => continue,
-                }
+    // the kind of move, which 'Move::new' stores in the move. Castling is either the case if
+    // - the king moves onto one of its rooks it may castle with or
+    // - the king moves two fields from the e-file to the c- or g-file (not in Chess960)
+    // whether castling or en passant is actually possible is checked by 'is_move_legal'
+    fn move_flag(&self, chess_move: &Move) -> MoveFlag {
+        let start = chess_move.start_field();
+        let target = chess_move.target_field();
+        match chess_move.piece().piecetype() {
+            PieceType::King => match self.castling_side(chess_move) {
+                Some(true) => MoveFlag::KingsideCastle,
+                Some(false) => MoveFlag::QueensideCastle,
+                None => MoveFlag::Normal,
+            },
+            PieceType::Pawn if chess_move.rank_distance() == 2 && start.1 == target.1 => {
+                MoveFlag::DoublePush
+            }
+            PieceType::Pawn
+                if chess_move.file_distance() == 1
+                    && self.en_passant.as_ref() == Some(target)
+                    && !self.board.has_piece_on_field(target) =>
+            {
+                MoveFlag::EnPassant
             }
+            _ => MoveFlag::Normal,
         }
+    }
 
-        false
+    fn captured_piece(&self, chess_move: &Move) -> Option<Piece> {
+        let color = chess_move.piece().color();
+        if chess_move.flag() == &MoveFlag::EnPassant {
+            let opposite_color = match color {
+                Color::White => Color::Black,
+                _ => Color::White,
+            };
+            return Some(Piece::new(opposite_color, PieceType::Pawn));
+        }
+        let piece = self.board.get_piece_on_field(chess_move.target_field());
+        match piece.color() {
+            Color::None => None,
+            piece_color if piece_color == color => None,
+            _ => Some(piece),
+        }
     }
 
-    fn move_rook_when_castling(&self, chess_move: &Move) {
-        if chess_move.piece().piecetype() != &PieceType::King {
-            return; // not castling
+    // whether the king castles on the kingside (true) or queenside (false)
+    fn castling_side(&self, chess_move: &Move) -> Option<bool> {
+        let color = chess_move.piece().color();
+        let home_rank = match color {
+            Color::White => 0,
+            Color::Black => 7,
+            Color::None => return None,
+        };
+        let start = chess_move.start_field();
+        let target = chess_move.target_field();
+        if start.0 != home_rank || target.0 != home_rank {
+            return None;
         }
 
-        let distance = chess_move.file_distance();
-        if distance < 2 {
-            return; // not castling
+        // in Chess960 the side is the one of the rook the king moves onto
+        let onto_rook = self.chess960
+            && self.board().get_piece_on_field(target) == Piece::new(*color, PieceType::Rook);
+        if onto_rook && self.castle_availability.rook_file(color, true) == Some(target.1) {
+            Some(true)
+        } else if onto_rook && self.castle_availability.rook_file(color, false) == Some(target.1) {
+            Some(false)
+        } else if !self.chess960 && start.1 == 4 && (target.1 == 6 || target.1 == 2) {
+            Some(target.1 == 6)
+        } else {
+            None
         }
+    }
 
-        let direction = chess_move.file_difference() / distance as isize;
+    // the fields king and rook move to, if the move is castling
+    fn castling(&self, chess_move: &Move) -> Option<Castling> {
+        let kingside = match chess_move.flag() {
+            MoveFlag::KingsideCastle => true,
+            MoveFlag::QueensideCastle => false,
+            _ => return None,
+        };
+        let home_rank = chess_move.start_field().0;
+        let color = chess_move.piece().color();
 
-        // remove rook
-        let rook_start_field = match direction {
-            1 => Field(chess_move.target_field().0, 7),
-            -1 => Field(chess_move.target_field().0, 0),
-            _ => panic!("Something terrible happened: {:?}", direction),
+        // no matter where king and rook start, they end up on the same fields as in standard chess
+        let (rook_file, king_file, rook_target_file) = match kingside {
+            true => (self.castle_availability.rook_file(color, true).unwrap_or(7), 6, 5),
+            false => (self.castle_availability.rook_file(color, false).unwrap_or(0), 2, 3),
         };
-        let rook = self
-            .position_matrix()
-            .borrow_mut()
-            .remove_piece_from_field(&rook_start_field);
+        Some(Castling {
+            king_target: Field(home_rank, king_file),
+            rook_start: Field(home_rank, rook_file),
+            rook_target: Field(home_rank, rook_target_file),
+        })
+    }
 
-        // place rook again
-        let rook_target_field = Field(
-            chess_move.target_field().0,
-            (chess_move.target_field().1 as isize - direction) as usize,
-        );
-        self.position_matrix()
-            .borrow_mut()
-            .place_piece(rook, &rook_target_field);
+    pub fn execute_move(&self, chess_move: &Move) -> State {
+        // the current board serves as the basis of the next state, but itself is left as-is.
+        let mut new_state = self.clone();
+        new_state.make_move(chess_move);
+        new_state
     }
 
-    fn remove_enemy_pawn_en_passant(&self, chess_move: &Move) {
-        if chess_move.piece().piecetype() != &PieceType::Pawn {
-            return; // not en-passant
-        }
+    // plays the move on this state, the returned info allows 'unmake_move' to take it back
+    pub fn make_move(&mut self, chess_move: &Move) -> UndoInfo {
+        let undo_info = UndoInfo {
+            castling: self.castling(chess_move),
+            castle_availability: self.castle_availability.clone(),
+            en_passant: self.en_passant.clone(),
+            halfmove_clock: self.halfmove_clock,
+            fullmove_clock: self.fullmove_clock,
+            zobrist_key: self.zobrist_key,
+        };
 
-        let target = chess_move.target_field();
+        // castling rights and en passant field are xor'ed out now and back in after the move
+        self.zobrist_key ^= zobrist::castling_key(&self.castle_availability)
+            ^ zobrist::en_passant_key(&self.en_passant, &self.board, &self.turn);
+
+        if let Some(castling) = &undo_info.castling {
+            // king and rook may swap fields in Chess960, so both are taken off the board first
+            let king = self.board.remove_piece_from_field(&chess_move.start_field);
+            let rook = self.board.remove_piece_from_field(&castling.rook_start);
+            self.board.place_piece(king, &castling.king_target);
+            self.board.place_piece(rook, &castling.rook_target);
+            self.zobrist_key ^= zobrist::piece_key(&king, &chess_move.start_field)
+                ^ zobrist::piece_key(&rook, &castling.rook_start)
+                ^ zobrist::piece_key(&king, &castling.king_target)
+                ^ zobrist::piece_key(&rook, &castling.rook_target);
+        } else {
+            // take the piece that is moving and the one it captures
+            self.board.remove_piece_from_field(&chess_move.start_field);
+            if let Some(field) = chess_move.captured_field() {
+                self.board.remove_piece_from_field(&field);
+            }
 
-        match self.en_passant() {
-            None => return, // not en-passant
-            Some(field) => {
-                // the en passant position in FEN notation is the field
-                // behind the pawn that just moved two squares,
-                // i.e. the field that the enemy pawn can move
-                if field != target {
-                    return; // not en-passant
-                }
+            // place it on the new field
+            self.board.place_piece(chess_move.piece, &chess_move.target_field);
+
+            // a promoted pawn is replaced by the chosen piece
+            if let Some(piecetype) = chess_move.promotion {
+                self.board.place_piece(
+                    Piece::new(chess_move.piece.color, piecetype),
+                    &chess_move.target_field,
+                );
+            }
+
+            self.zobrist_key ^= zobrist::piece_key(&chess_move.piece, &chess_move.start_field)
+                ^ zobrist::piece_key(
+                    &self.board.get_piece_on_field(&chess_move.target_field),
+                    &chess_move.target_field,
+                );
+            if let (Some(piece), Some(field)) = (chess_move.captured, chess_move.captured_field()) {
+                self.zobrist_key ^= zobrist::piece_key(&piece, &field);
             }
         }
 
-        // we need to remove the pawn from the field
-        // just below/above the target field (depending on the color)
-        if self.turn() == &Color::White {
-            self.position_matrix()
-                .borrow_mut()
-                .remove_piece_from_field(&Field(target.0 - 1, target.1));
-        } else {
-            self.position_matrix()
-                .borrow_mut()
-                .remove_piece_from_field(&Field(target.0 + 1, target.1));
+        self.update_castling_availability(chess_move);
+        self.update_en_passant(chess_move);
+
+        // turn the clocks (they stop at their maximum, which any FEN string may give)
+        if chess_move.is_capture() || chess_move.piece().piecetype() == &PieceType::Pawn {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+
+        if self.turn() == &Color::Black {
+            self.fullmove_clock = self.fullmove_clock.saturating_add(1);
         }
+
+        self.toggle_turn();
+        self.zobrist_key ^= zobrist::castling_key(&self.castle_availability)
+            ^ zobrist::en_passant_key(&self.en_passant, &self.board, &self.turn)
+            ^ zobrist::turn_key(&Color::White);
+        undo_info
     }
 
-    fn execute_move(&self, chess_move: &Move) -> State {
-        // the current board serves as the basis of the next state, but itself is left as-is.
-        let mut new_state = self.clone();
+    // takes back the move, which has to be the last move made on this state
+    pub fn unmake_move(&mut self, chess_move: &Move, undo_info: UndoInfo) {
+        self.toggle_turn();
 
-        // take the piece that is moving
-        new_state
-            .position_matrix()
-            .borrow_mut()
-            .remove_piece_from_field(&chess_move.start_field);
-
-        // place it on the new field and take the piece that was on it
-        let captured_piece: Piece = new_state
-            .position_matrix()
-            .borrow_mut()
-            .place_piece(chess_move.piece, &chess_move.target_field);
-
-        // castling and en-passant need to be handled separately
-        new_state.move_rook_when_castling(chess_move);
-        new_state.update_castling_availability(chess_move);
-        new_state.remove_enemy_pawn_en_passant(chess_move);
-        new_state.update_en_passant(chess_move);
-
-        // turn the clocks
-        if captured_piece.piecetype() != &PieceType::None
-            || chess_move.piece().piecetype() == &PieceType::Pawn
-        {
-            new_state.halfmove_clock = 0;
+        if let Some(castling) = &undo_info.castling {
+            let king = self.board.remove_piece_from_field(&castling.king_target);
+            let rook = self.board.remove_piece_from_field(&castling.rook_target);
+            self.board.place_piece(king, &chess_move.start_field);
+            self.board.place_piece(rook, &castling.rook_start);
         } else {
-            new_state.halfmove_clock += 1;
-        }
+            // put the moving piece (a pawn in case of a promotion) back on its start field
+            self.board.remove_piece_from_field(&chess_move.target_field);
+            self.board.place_piece(chess_move.piece, &chess_move.start_field);
 
-        if new_state.turn() == &Color::Black {
-            new_state.fullmove_clock += 1;
+            if let Some(field) = chess_move.captured_field() {
+                self.board.place_piece(chess_move.captured.unwrap(), &field);
+            }
         }
 
-        new_state.toggle_turn();
-        new_state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(new_state.position_matrix().borrow());
-
-        new_state
+        self.castle_availability = undo_info.castle_availability;
+        self.en_passant = undo_info.en_passant;
+        self.halfmove_clock = undo_info.halfmove_clock;
+        self.fullmove_clock = undo_info.fullmove_clock;
+        self.zobrist_key = undo_info.zobrist_key;
     }
 
     fn update_en_passant(&mut self, chess_move: &Move) {
-        if chess_move.piece().piecetype() != &PieceType::Pawn || chess_move.rank_distance() < 2 {
-            self.en_passant = None;
-            return;
-        }
-
         // the en passant field is the field between the pawns starting and target field
-        self.en_passant = match *self.turn() {
-            Color::White => Some(Field(
-                chess_move.start_field().0 + 1,
-                chess_move.start_field().1,
-            )),
-            Color::Black => Some(Field(
-                chess_move.start_field().0 - 1,
+        self.en_passant = match chess_move.flag() {
+            MoveFlag::DoublePush => Some(Field(
+                (chess_move.start_field().0 + chess_move.target_field().0) / 2,
                 chess_move.start_field().1,
             )),
-            _ => panic!("Invalid game state, no turn. {:?}", self),
+            _ => None,
         };
     }
 
     fn update_castling_availability(&mut self, chess_move: &Move) {
-        if chess_move.piece().piecetype() != &PieceType::Rook
-            && chess_move.piece().piecetype() != &PieceType::King
-        {
-            return; // nothing to do here
-        }
-
         if chess_move.piece().piecetype() == &PieceType::King {
-            if self.turn() == &Color::Black {
-                self.castle_availability.black_king = false;
-                self.castle_availability.black_queen = false;
-            } else {
-                self.castle_availability.white_king = false;
-                self.castle_availability.white_queen = false;
+            if let Some(rook_file) = self.castle_availability.rook_file_mut(&self.turn, true) {
+                *rook_file = None;
             }
-            return;
-        }
-
-        if chess_move.start_field().1 == 0 {
-            if self.turn() == &Color::Black {
-                self.castle_availability.black_queen = false;
-            } else {
-                self.castle_availability.white_queen = false;
+            if let Some(rook_file) = self.castle_availability.rook_file_mut(&self.turn, false) {
+                *rook_file = None;
             }
-            return;
         }
 
-        if chess_move.start_field().1 == 7 {
-            if self.turn() == &Color::Black {
-                self.castle_availability.black_king = false;
-            } else {
-                self.castle_availability.white_king = false;
-            }
-        }
+        self.castle_availability.remove_rook(chess_move.start_field());
+        self.castle_availability.remove_rook(chess_move.target_field());
     }
 
     fn toggle_turn(&mut self) {
@@ -1088,15 +2064,8 @@ impl<'a> State {
         }
     }
 
-    fn get_king_field(position_matrix: Ref<PositionMatrix>, color: &Color) -> Option<Field> {
-        for (i, rank) in position_matrix.0.iter().enumerate() {
-            for (j, piece) in rank.iter().enumerate() {
-                if piece.color() == color && piece.piecetype() == &PieceType::King {
-                    return Some(Field(i, j));
-                }
-            }
-        }
-        None
+    fn get_king_field(board: &Board, color: &Color) -> Option<Field> {
+        board.king_field(color)
     }
 
     fn is_player_in_check(&self, color: &Color) -> bool {
@@ -1112,7 +2081,7 @@ impl<'a> State {
         };
 
         let king_field: Field =
-            State::get_king_field(self.position_matrix().borrow(), player_color).unwrap();
+            State::get_king_field(self.board(), player_color).unwrap();
 
         self.is_players_piece_attacking_field(&enemy_color, &king_field)
     }
@@ -1127,228 +2096,228 @@ mod tests {
     fn new_game() {
         let state = State::new(None);
         assert_eq!(
-            state.position().borrow().0,
+            state.position().0,
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"
         );
         assert_eq!(
-            state.position_matrix().borrow().0[0][0],
+            state.position_matrix().0[0][0],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Rook
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[0][1],
+            state.position_matrix().0[0][1],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Knight
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[0][2],
+            state.position_matrix().0[0][2],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Bishop
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[0][3],
+            state.position_matrix().0[0][3],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Queen
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[0][4],
+            state.position_matrix().0[0][4],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::King
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[0][5],
+            state.position_matrix().0[0][5],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Bishop
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[0][6],
+            state.position_matrix().0[0][6],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Knight
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[0][7],
+            state.position_matrix().0[0][7],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Rook
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[1][0],
+            state.position_matrix().0[1][0],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[1][1],
+            state.position_matrix().0[1][1],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[1][2],
+            state.position_matrix().0[1][2],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[1][3],
+            state.position_matrix().0[1][3],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[1][4],
+            state.position_matrix().0[1][4],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[1][5],
+            state.position_matrix().0[1][5],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[1][6],
+            state.position_matrix().0[1][6],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[1][7],
+            state.position_matrix().0[1][7],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[6][0],
+            state.position_matrix().0[6][0],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[6][1],
+            state.position_matrix().0[6][1],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[6][2],
+            state.position_matrix().0[6][2],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[6][3],
+            state.position_matrix().0[6][3],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[6][4],
+            state.position_matrix().0[6][4],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[6][5],
+            state.position_matrix().0[6][5],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[6][6],
+            state.position_matrix().0[6][6],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[6][7],
+            state.position_matrix().0[6][7],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[7][0],
+            state.position_matrix().0[7][0],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Rook
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[7][1],
+            state.position_matrix().0[7][1],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Knight
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[7][2],
+            state.position_matrix().0[7][2],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Bishop
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[7][3],
+            state.position_matrix().0[7][3],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Queen
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[7][4],
+            state.position_matrix().0[7][4],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::King
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[7][5],
+            state.position_matrix().0[7][5],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Bishop
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[7][6],
+            state.position_matrix().0[7][6],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Knight
             }
         );
         assert_eq!(
-            state.position_matrix().borrow().0[7][7],
+            state.position_matrix().0[7][7],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Rook
@@ -1358,20 +2327,17 @@ mod tests {
 
     #[test]
     fn matrix_take_piece() {
-        let state = State::new(None);
+        let mut state = State::new(None);
         assert!(
-            state.position_matrix().borrow().0[0][0]
+            state.position_matrix().0[0][0]
                 != Piece {
                     color: Color::None,
                     piecetype: PieceType::None
                 }
         );
-        state
-            .position_matrix()
-            .borrow_mut()
-            .remove_piece_from_field(&Field(0, 0));
+        state.board.remove_piece_from_field(&Field(0, 0));
         assert!(
-            state.position_matrix().borrow().0[0][0]
+            state.position_matrix().0[0][0]
                 == Piece {
                     color: Color::None,
                     piecetype: PieceType::None
@@ -1387,7 +2353,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert_eq!(
             chess_move.piece,
@@ -1404,7 +2370,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert_eq!(
             chess_move.piece,
@@ -1426,18 +2392,18 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         let chess_move_check = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
 
         let next_state = state.execute_move(&chess_move);
 
         assert_eq!(
-            next_state.position_matrix().borrow().0[chess_move_check.start_field.0]
+            next_state.position_matrix().0[chess_move_check.start_field.0]
                 [chess_move_check.start_field.1],
             Piece {
                 color: Color::None,
@@ -1445,7 +2411,7 @@ mod tests {
             }
         );
         assert_eq!(
-            next_state.position_matrix().borrow().0[chess_move_check.target_field.0]
+            next_state.position_matrix().0[chess_move_check.target_field.0]
                 [chess_move_check.target_field.1],
             chess_move_check.piece
         );
@@ -1455,7 +2421,7 @@ mod tests {
     fn update_position_from_matrix() {
         let state = State::new(None);
         assert_eq!(
-            state.position().borrow().0,
+            state.position().0,
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"
         );
 
@@ -1465,15 +2431,11 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         let mut next_state = state.execute_move(&chess_move);
-        next_state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(next_state.position_matrix().borrow());
         assert_eq!(
-            next_state.position().borrow().0,
+            next_state.position().0,
             "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR"
         );
 
@@ -1483,15 +2445,11 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         next_state = next_state.execute_move(&chess_move);
-        next_state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(next_state.position_matrix().borrow());
         assert_eq!(
-            next_state.position().borrow().0,
+            next_state.position().0,
             "r1bqkbnr/pppppppp/2n5/8/4P3/8/PPPP1PPP/RNBQKBNR"
         );
     }
@@ -1506,7 +2464,7 @@ mod tests {
         let legal_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.is_move_legal(&legal_move).unwrap());
 
@@ -1516,7 +2474,7 @@ mod tests {
         let illegal_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(match state.is_move_legal(&illegal_move) {
             Ok(_) => false,
@@ -1541,7 +2499,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1551,7 +2509,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1561,7 +2519,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1571,7 +2529,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1581,7 +2539,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1591,7 +2549,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1601,7 +2559,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1611,7 +2569,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1621,7 +2579,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
     }
@@ -1636,7 +2594,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1646,7 +2604,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1656,7 +2614,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1666,7 +2624,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1676,7 +2634,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1686,7 +2644,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1703,7 +2661,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1713,7 +2671,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1723,7 +2681,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1733,7 +2691,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1743,7 +2701,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1753,7 +2711,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1763,7 +2721,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1773,7 +2731,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1783,7 +2741,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
     }
@@ -1798,7 +2756,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1808,7 +2766,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1818,7 +2776,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1828,7 +2786,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1838,7 +2796,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1848,7 +2806,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1858,7 +2816,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1868,7 +2826,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
     }
@@ -1883,7 +2841,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1893,7 +2851,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1903,7 +2861,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1913,7 +2871,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1923,7 +2881,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1933,7 +2891,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1943,7 +2901,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -1953,7 +2911,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
     }
@@ -1968,7 +2926,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1978,7 +2936,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1988,7 +2946,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -1998,7 +2956,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -2008,7 +2966,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -2018,7 +2976,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -2028,7 +2986,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -2038,7 +2996,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -2048,7 +3006,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move).unwrap());
 
@@ -2058,7 +3016,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
 
@@ -2068,7 +3026,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(state.piece_can_reach_target_field(&chess_move) == Err(MoveError::PieceCantReachTarget));
     }
@@ -2085,7 +3043,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(State::piece_has_path_to_target_field(&state, &chess_move));
     }
@@ -2101,7 +3059,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(State::piece_has_path_to_target_field(&state, &chess_move));
 
@@ -2110,7 +3068,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(!State::piece_has_path_to_target_field(&state, &chess_move));
 
@@ -2119,7 +3077,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(!State::piece_has_path_to_target_field(&state, &chess_move));
     }
@@ -2135,7 +3093,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(State::piece_has_path_to_target_field(&state, &chess_move));
 
@@ -2145,7 +3103,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            new_state.position_matrix().borrow(),
+            &new_state,
         );
         assert!(!State::piece_has_path_to_target_field(
             &new_state,
@@ -2164,7 +3122,7 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(State::piece_has_path_to_target_field(&state, &chess_move));
 
@@ -2173,14 +3131,13 @@ mod tests {
         let chess_move = Move::new(
             &start_field,
             &target_field,
-            state.position_matrix().borrow(),
+            &state,
         );
         assert!(!State::piece_has_path_to_target_field(&state, &chess_move));
     }
 
     /*
-       position: RefCell<Position>,
-       position_matrix:  RefCell<PositionMatrix>,
+       board: Board,
        turn: Color,
        castle_availability: CastleAvailability,
        en_passant: Option<Field>,
@@ -2193,14 +3150,14 @@ mod tests {
             String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
         let state = State::load_game_from_fen(fen_start_string);
         assert_eq!(
-            state.position().borrow().0,
+            state.position().0,
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"
         );
         assert_eq!(state.turn, Color::White);
-        assert!(state.castle_availability().white_king);
-        assert!(state.castle_availability().white_queen);
-        assert!(state.castle_availability().black_king);
-        assert!(state.castle_availability().black_queen);
+        assert!(state.castle_availability().white_king.is_some());
+        assert!(state.castle_availability().white_queen.is_some());
+        assert!(state.castle_availability().black_king.is_some());
+        assert!(state.castle_availability().black_queen.is_some());
         assert_eq!(state.en_passant, None);
         assert_eq!(state.halfmove_clock, 0);
         assert_eq!(state.fullmove_clock, 1);
@@ -2212,14 +3169,14 @@ mod tests {
             String::from("rnbqkbnr/pp1ppppp/8/2p5/4P3/2P5/PP1P1PPP/RNBQKBNR b KQkq - 0 2");
         let state = State::load_game_from_fen(fen_start_string);
         assert_eq!(
-            state.position().borrow().0,
+            state.position().0,
             "rnbqkbnr/pp1ppppp/8/2p5/4P3/2P5/PP1P1PPP/RNBQKBNR"
         );
         assert_eq!(state.turn, Color::Black);
-        assert!(state.castle_availability().white_king);
-        assert!(state.castle_availability().white_queen);
-        assert!(state.castle_availability().black_king);
-        assert!(state.castle_availability().black_queen);
+        assert!(state.castle_availability().white_king.is_some());
+        assert!(state.castle_availability().white_queen.is_some());
+        assert!(state.castle_availability().black_king.is_some());
+        assert!(state.castle_availability().black_queen.is_some());
         assert_eq!(state.en_passant, None);
         assert_eq!(state.halfmove_clock, 0);
         assert_eq!(state.fullmove_clock, 2);
@@ -2231,14 +3188,14 @@ mod tests {
             String::from("r6k/1p1q3p/3r1pp1/b1R1N3/p2PQ3/P5P1/1P3P1P/3R2K1 b - - 0 28");
         let state = State::load_game_from_fen(fen_start_string);
         assert_eq!(
-            state.position().borrow().0,
+            state.position().0,
             "r6k/1p1q3p/3r1pp1/b1R1N3/p2PQ3/P5P1/1P3P1P/3R2K1"
         );
         assert_eq!(state.turn, Color::Black);
-        assert!(!state.castle_availability().white_king);
-        assert!(!state.castle_availability().white_queen);
-        assert!(!state.castle_availability().black_king);
-        assert!(!state.castle_availability().black_queen);
+        assert!(state.castle_availability().white_king.is_none());
+        assert!(state.castle_availability().white_queen.is_none());
+        assert!(state.castle_availability().black_king.is_none());
+        assert!(state.castle_availability().black_queen.is_none());
         assert_eq!(state.en_passant, None);
         assert_eq!(state.halfmove_clock, 0);
         assert_eq!(state.fullmove_clock, 28);
@@ -2250,14 +3207,14 @@ mod tests {
             String::from("rnbqkb1r/pp3ppp/3p1n2/2pPp3/4P3/2N5/PPP2PPP/R1BQKBNR w KQkq c6 0 5");
         let state = State::load_game_from_fen(fen_start_string);
         assert_eq!(
-            state.position().borrow().0,
+            state.position().0,
             "rnbqkb1r/pp3ppp/3p1n2/2pPp3/4P3/2N5/PPP2PPP/R1BQKBNR"
         );
         assert_eq!(state.turn, Color::White);
-        assert!(state.castle_availability().white_king);
-        assert!(state.castle_availability().white_queen);
-        assert!(state.castle_availability().black_king);
-        assert!(state.castle_availability().black_queen);
+        assert!(state.castle_availability().white_king.is_some());
+        assert!(state.castle_availability().white_queen.is_some());
+        assert!(state.castle_availability().black_king.is_some());
+        assert!(state.castle_availability().black_queen.is_some());
         assert_eq!(state.en_passant, Some(Field(5, 2)));
         assert_eq!(state.halfmove_clock, 0);
         assert_eq!(state.fullmove_clock, 5);
@@ -2269,19 +3226,116 @@ mod tests {
             String::from("rnbq1rk1/p3bppp/2pp1n2/4p1B1/2B1P3/2N5/PPP2PPP/R2QK1NR w KQ - 4 8");
         let state = State::load_game_from_fen(fen_start_string);
         assert_eq!(
-            state.position().borrow().0,
+            state.position().0,
             "rnbq1rk1/p3bppp/2pp1n2/4p1B1/2B1P3/2N5/PPP2PPP/R2QK1NR"
         );
         assert_eq!(state.turn, Color::White);
-        assert!(state.castle_availability().white_king);
-        assert!(state.castle_availability().white_queen);
-        assert!(!state.castle_availability().black_king);
-        assert!(!state.castle_availability().black_queen);
+        assert!(state.castle_availability().white_king.is_some());
+        assert!(state.castle_availability().white_queen.is_some());
+        assert!(state.castle_availability().black_king.is_none());
+        assert!(state.castle_availability().black_queen.is_none());
         assert_eq!(state.en_passant, None);
         assert_eq!(state.halfmove_clock, 4);
         assert_eq!(state.fullmove_clock, 8);
     }
 
+    #[test]
+    fn from_fen_castling_any_order() {
+        let state = State::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w qkQ - 0 1").unwrap();
+        assert!(state.castle_availability().white_king.is_none());
+        assert!(state.castle_availability().white_queen.is_some());
+        assert!(state.castle_availability().black_king.is_some());
+        assert!(state.castle_availability().black_queen.is_some());
+    }
+
+    #[test]
+    fn to_fen_round_trip() {
+        let fen_strings = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "rnbq1rk1/p3bppp/2pp1n2/4p1B1/2B1P3/2N5/PPP2PPP/R2QK1NR w KQ - 4 8",
+            "8/8/4kB1P/PP1p3R/6N1/2r5/1r6/6K1 b - - 0 1",
+        ];
+        for fen in fen_strings.iter() {
+            assert_eq!(State::from_fen(fen).unwrap().to_fen(), *fen);
+        }
+
+        // the en passant field and clocks are updated by moves
+        let state = State::new(None);
+        let chess_move = Move::new(&Field(1, 4), &Field(3, 4), &state);
+        assert_eq!(
+            state.execute_move(&chess_move).to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+    }
+
+    #[test]
+    fn from_fen_errors() {
+        let errors = [
+            ("8/8/8/8/8/8/8/8 w - - 0", FenError::WrongNumberOfFields(5)),
+            ("8/8/8/8/8/8/8 w - - 0 1", FenError::WrongNumberOfRanks(7)),
+            ("8/8/8/8/8/8/8/7 w - - 0 1", FenError::BadRankLength(1)),
+            ("8/8/8/8/8/8/8/5k2P w - - 0 1", FenError::BadRankLength(1)),
+            ("8/8/8/8/8/8/8/3x4 w - - 0 1", FenError::UnknownPiece('x')),
+            ("8/8/8/8/8/8/8/8 x - - 0 1", FenError::InvalidTurn(String::from("x"))),
+            ("8/8/8/8/8/8/8/8 w KK - 0 1", FenError::InvalidCastling(String::from("KK"))),
+            ("8/8/8/8/8/8/8/8 w Kx - 0 1", FenError::InvalidCastling(String::from("Kx"))),
+            ("8/8/8/8/8/8/8/8 w - e4 0 1", FenError::InvalidEnPassant(String::from("e4"))),
+            ("8/8/8/8/8/8/8/8 w - i6 0 1", FenError::InvalidEnPassant(String::from("i6"))),
+            ("8/8/8/8/8/8/8/8 w - - x 1", FenError::InvalidHalfmoveClock(String::from("x"))),
+            ("8/8/8/8/8/8/8/8 w - - 0 -1", FenError::InvalidFullmoveClock(String::from("-1"))),
+            ("8/8/8/8/8/8/8/8 w - - 0 0", FenError::InvalidFullmoveClock(String::from("0"))),
+        ];
+        for (fen, error) in errors.iter() {
+            assert_eq!(State::from_fen(fen).unwrap_err(), *error, "{}", fen);
+        }
+
+        // the clocks stop at their maximum instead of overflowing
+        let fen = "4k3/8/8/8/8/8/8/4K3 b - - 65535 65535";
+        let mut state = State::from_fen(fen).unwrap();
+        let chess_move = state.parse_san("Kd8").unwrap();
+        let undo_info = state.make_move(&chess_move);
+        assert_eq!(state.halfmove_clock(), 65535);
+        assert_eq!(state.fullmove_clock(), 65535);
+        state.unmake_move(&chess_move, undo_info);
+        assert_eq!(state.to_fen(), fen);
+    }
+
+    #[test]
+    fn validate() {
+        let errors = [
+            ("8/8/8/8/8/8/8/4K3 w - - 0 1", PositionError::WrongNumberOfKings(Color::Black, 0)),
+            ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", PositionError::WrongNumberOfKings(Color::White, 2)),
+            ("4k3/8/8/8/8/8/8/P3K3 w - - 0 1", PositionError::PawnOnBackRank(Field(0, 0))),
+            ("4k2p/8/8/8/8/8/8/4K3 w - - 0 1", PositionError::PawnOnBackRank(Field(7, 7))),
+            ("4k2Q/8/8/8/8/8/8/4K3 w - - 0 1", PositionError::OpponentInCheck),
+            ("4k3/8/8/8/8/8/4K3/7R w K - 0 1", PositionError::ImpossibleCastling(Color::White, 7)),
+            ("4k3/8/8/8/8/8/8/R3K3 w K - 0 1", PositionError::ImpossibleCastling(Color::White, 7)),
+            ("4k2r/8/8/8/8/8/8/4K3 w kq - 0 1", PositionError::ImpossibleCastling(Color::Black, 0)),
+            ("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1", PositionError::ImpossibleEnPassant(Field(2, 4))),
+            ("4k3/8/8/3p4/8/8/8/4K3 w - e6 0 1", PositionError::ImpossibleEnPassant(Field(5, 4))),
+            ("4k3/4p3/8/4p3/8/8/8/4K3 w - e6 0 1", PositionError::ImpossibleEnPassant(Field(5, 4))),
+        ];
+        for (fen, error) in errors.iter() {
+            assert_eq!(
+                State::from_fen(fen),
+                Err(FenError::InvalidPosition(error.clone())),
+                "{}",
+                fen
+            );
+        }
+
+        let valid = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 2",
+            "r4k1r/8/8/8/8/8/8/RK5R w AHah - 0 1",
+        ];
+        for fen in valid.iter() {
+            assert_eq!(State::from_fen(fen).unwrap().validate(), Ok(()), "{}", fen);
+        }
+    }
+
     #[test]
     fn is_player_in_check_01() {
         let fen_string =
@@ -2314,7 +3368,7 @@ mod tests {
         let fen_string =
             String::from("r1bqkbnr/ppp2ppp/2np4/1B2p3/4P3/2N2N2/PPPP1PPP/R1BQK2R b KQkq - 1 4");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(5, 2), &Field(3, 3), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(5, 2), &Field(3, 3), &state);
         assert!(match state.is_move_legal(&chess_move) {
             Ok(_) => false,
             Err(e) if e == MoveError::PieceIsPinned => true,
@@ -2327,7 +3381,7 @@ mod tests {
         let fen_string =
             String::from("r2qkbnr/pppb1ppp/2np4/1B2p3/4P3/2N2N1P/PPPP1PP1/R1BQK2R b KQkq - 0 5");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(5, 2), &Field(3, 3), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(5, 2), &Field(3, 3), &state);
         assert!(state.is_move_legal(&chess_move).unwrap());
     }
 
@@ -2336,7 +3390,7 @@ mod tests {
         let fen_string =
             String::from("r2qkbnr/pppb1ppp/3p4/1B2p3/3nP3/2N2N1P/PPPP1PP1/R1BQK2R w KQkq - 1 6");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(0, 4), &Field(1, 4), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(0, 4), &Field(1, 4), &state);
         assert!(match state.is_move_legal(&chess_move) {
             Ok(_) => false,
             Err(e) if e == MoveError::MovingIntoCheck => true,
@@ -2349,7 +3403,7 @@ mod tests {
         let fen_string =
             String::from("r2qkbnr/pppb1ppp/2np4/1B2p3/4P3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 2 5");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(0, 4), &Field(1, 4), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(0, 4), &Field(1, 4), &state);
         assert!(state.is_move_legal(&chess_move).unwrap());
     }
 
@@ -2398,7 +3452,7 @@ mod tests {
         let fen_string =
             String::from("r1bqkb1r/ppp1pppp/2n2n2/1B6/8/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 6 6");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(0, 4), &Field(0, 6), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(0, 4), &Field(0, 6), &state);
         assert!(!state.is_castling_through_check(&chess_move));
     }
 
@@ -2407,7 +3461,7 @@ mod tests {
         let fen_string =
             String::from("r1bqk2r/ppp2ppp/2B1pn2/8/1b6/2N2N2/PPPP1PPP/R1B1QRK1 b kq - 0 8");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(7, 4), &Field(7, 6), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(7, 4), &Field(7, 6), &state);
         assert!(state.is_castling_through_check(&chess_move));
     }
 
@@ -2416,7 +3470,7 @@ mod tests {
         let fen_string =
             String::from("r2qk2r/p1p2ppp/b1p1pn2/8/8/BPP2N2/P1P2PPP/R3QRK1 b kq - 2 11");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(7, 4), &Field(7, 6), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(7, 4), &Field(7, 6), &state);
         assert!(state.is_castling_through_check(&chess_move));
     }
 
@@ -2425,25 +3479,21 @@ mod tests {
         let fen_string =
             String::from("r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(0, 4), &Field(0, 6), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(0, 4), &Field(0, 6), &state);
         let new_state = state.execute_move(&chess_move);
-        new_state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(new_state.position_matrix().borrow());
         assert_eq!(
-            new_state.position().borrow().0,
+            new_state.position().0,
             "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1"
         );
         assert_eq!(
-            new_state.position_matrix().borrow().0[0][5],
+            new_state.position_matrix().0[0][5],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Rook
             }
         );
         assert_eq!(
-            new_state.position_matrix().borrow().0[0][6],
+            new_state.position_matrix().0[0][6],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::King
@@ -2456,25 +3506,21 @@ mod tests {
         let fen_string =
             String::from("rn1qk2r/pppbbppp/5n2/4p3/N2p4/1P1P4/PBPQPPPP/R3KBNR w KQkq - 3 7");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(0, 4), &Field(0, 2), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(0, 4), &Field(0, 2), &state);
         let new_state = state.execute_move(&chess_move);
-        new_state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(new_state.position_matrix().borrow());
         assert_eq!(
-            new_state.position().borrow().0,
+            new_state.position().0,
             "rn1qk2r/pppbbppp/5n2/4p3/N2p4/1P1P4/PBPQPPPP/2KR1BNR"
         );
         assert_eq!(
-            new_state.position_matrix().borrow().0[0][2],
+            new_state.position_matrix().0[0][2],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::King
             }
         );
         assert_eq!(
-            new_state.position_matrix().borrow().0[0][3],
+            new_state.position_matrix().0[0][3],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Rook
@@ -2487,25 +3533,21 @@ mod tests {
         let fen_string =
             String::from("rnbqk2r/pppp1ppp/5n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(7, 4), &Field(7, 6), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(7, 4), &Field(7, 6), &state);
         let new_state = state.execute_move(&chess_move);
-        new_state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(new_state.position_matrix().borrow());
         assert_eq!(
-            new_state.position().borrow().0,
+            new_state.position().0,
             "rnbq1rk1/pppp1ppp/5n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1"
         );
         assert_eq!(
-            new_state.position_matrix().borrow().0[7][5],
+            new_state.position_matrix().0[7][5],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Rook
             }
         );
         assert_eq!(
-            new_state.position_matrix().borrow().0[7][6],
+            new_state.position_matrix().0[7][6],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::King
@@ -2518,25 +3560,21 @@ mod tests {
         let fen_string =
             String::from("r3kbnr/pbpqpppp/1pnp4/8/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1 b kq - 0 6");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(7, 4), &Field(7, 2), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(7, 4), &Field(7, 2), &state);
         let new_state = state.execute_move(&chess_move);
-        new_state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(new_state.position_matrix().borrow());
         assert_eq!(
-            new_state.position().borrow().0,
+            new_state.position().0,
             "2kr1bnr/pbpqpppp/1pnp4/8/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1"
         );
         assert_eq!(
-            new_state.position_matrix().borrow().0[7][2],
+            new_state.position_matrix().0[7][2],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::King
             }
         );
         assert_eq!(
-            new_state.position_matrix().borrow().0[7][3],
+            new_state.position_matrix().0[7][3],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Rook
@@ -2552,37 +3590,33 @@ mod tests {
 
         // the left pawn moved the move before
         // en passant is only possible on f6
-        let chess_move = Move::new(&Field(4, 4), &Field(5, 3), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(4, 4), &Field(5, 3), &state);
         assert!(match state.is_move_legal(&chess_move) {
             Ok(_) => false,
             Err(e) if e == MoveError::PieceCantReachTarget => true,
             Err(_) => false,
         });
-        let chess_move = Move::new(&Field(4, 4), &Field(5, 5), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(4, 4), &Field(5, 5), &state);
         assert!(state.is_move_legal(&chess_move).unwrap());
 
         // check whether the en passant move execution works
         let new_state = state.execute_move(&chess_move);
-        new_state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(new_state.position_matrix().borrow());
         assert_eq!(
-            new_state.position_matrix().borrow().0[5][5],
+            new_state.position_matrix().0[5][5],
             Piece {
                 color: Color::White,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            new_state.position_matrix().borrow().0[4][5],
+            new_state.position_matrix().0[4][5],
             Piece {
                 color: Color::None,
                 piecetype: PieceType::None
             }
         );
         assert_eq!(
-            new_state.position().borrow().0,
+            new_state.position().0,
             "rnbqkbnr/ppp1p1pp/5P2/3p4/8/8/PPPP1PPP/RNBQKBNR"
         );
     }
@@ -2592,29 +3626,25 @@ mod tests {
         let fen_string =
             String::from("rnbq1rk1/1p1pppbp/5np1/2p5/pPB1P3/2NP1N2/P1PB1PPP/R2Q1RK1 b - b3 0 8");
         let state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(3, 0), &Field(2, 1), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(3, 0), &Field(2, 1), &state);
         assert!(state.is_move_legal(&chess_move).unwrap());
         let new_state = state.execute_move(&chess_move);
-        new_state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(new_state.position_matrix().borrow());
         assert_eq!(
-            new_state.position_matrix().borrow().0[2][1],
+            new_state.position_matrix().0[2][1],
             Piece {
                 color: Color::Black,
                 piecetype: PieceType::Pawn
             }
         );
         assert_eq!(
-            new_state.position_matrix().borrow().0[3][1],
+            new_state.position_matrix().0[3][1],
             Piece {
                 color: Color::None,
                 piecetype: PieceType::None
             }
         );
         assert_eq!(
-            new_state.position().borrow().0,
+            new_state.position().0,
             "rnbq1rk1/1p1pppbp/5np1/2p5/2B1P3/1pNP1N2/P1PB1PPP/R2Q1RK1"
         );
     }
@@ -2646,12 +3676,12 @@ mod tests {
         let fen_string =
             String::from("r1bq1rk1/pp1pppbp/2n2np1/2p5/2B1P3/1P3N2/PBPPQPPP/RN2K2R w KQ - 3 7");
         let mut state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(0, 7), &Field(0, 6), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(0, 7), &Field(0, 6), &state);
         state.update_castling_availability(&chess_move);
-        assert!(!state.castle_availability.white_king);
-        assert!(state.castle_availability.white_queen);
-        assert!(!state.castle_availability.black_king);
-        assert!(!state.castle_availability.black_queen);
+        assert!(state.castle_availability.white_king.is_none());
+        assert!(state.castle_availability.white_queen.is_some());
+        assert!(state.castle_availability.black_king.is_none());
+        assert!(state.castle_availability.black_queen.is_none());
     }
 
     #[test]
@@ -2659,12 +3689,12 @@ mod tests {
         let fen_string =
             String::from("r1b2rk1/pp1pppbp/1qn2np1/2p5/2B1P3/1PN2N2/PBPPQPPP/R3K2R w KQ - 5 8");
         let mut state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(0, 0), &Field(0, 1), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(0, 0), &Field(0, 1), &state);
         state.update_castling_availability(&chess_move);
-        assert!(state.castle_availability.white_king);
-        assert!(!state.castle_availability.white_queen);
-        assert!(!state.castle_availability.black_king);
-        assert!(!state.castle_availability.black_queen);
+        assert!(state.castle_availability.white_king.is_some());
+        assert!(state.castle_availability.white_queen.is_none());
+        assert!(state.castle_availability.black_king.is_none());
+        assert!(state.castle_availability.black_queen.is_none());
     }
 
     #[test]
@@ -2672,12 +3702,12 @@ mod tests {
         let fen_string =
             String::from("rnbqk1nr/pppp1ppp/8/2b1p3/2B1P3/8/PPPP1PPP/RNBQK1NR w KQkq - 2 3");
         let mut state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(0, 4), &Field(1, 4), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(0, 4), &Field(1, 4), &state);
         state.update_castling_availability(&chess_move);
-        assert!(!state.castle_availability.white_king);
-        assert!(!state.castle_availability.white_queen);
-        assert!(state.castle_availability.black_king);
-        assert!(state.castle_availability.black_queen);
+        assert!(state.castle_availability.white_king.is_none());
+        assert!(state.castle_availability.white_queen.is_none());
+        assert!(state.castle_availability.black_king.is_some());
+        assert!(state.castle_availability.black_queen.is_some());
     }
 
     #[test]
@@ -2685,12 +3715,12 @@ mod tests {
         let fen_string =
             String::from("rnbqk2r/pppp1ppp/5n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4");
         let mut state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(7, 7), &Field(7, 5), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(7, 7), &Field(7, 5), &state);
         state.update_castling_availability(&chess_move);
-        assert!(!state.castle_availability.white_king);
-        assert!(!state.castle_availability.white_queen);
-        assert!(!state.castle_availability.black_king);
-        assert!(state.castle_availability.black_queen);
+        assert!(state.castle_availability.white_king.is_none());
+        assert!(state.castle_availability.white_queen.is_none());
+        assert!(state.castle_availability.black_king.is_none());
+        assert!(state.castle_availability.black_queen.is_some());
     }
 
     #[test]
@@ -2698,12 +3728,12 @@ mod tests {
         let fen_string =
             String::from("r3kbnr/pbqppppp/1pn5/2p5/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1 b kq - 0 6");
         let mut state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(7, 0), &Field(7, 1), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(7, 0), &Field(7, 1), &state);
         state.update_castling_availability(&chess_move);
-        assert!(!state.castle_availability.white_king);
-        assert!(!state.castle_availability.white_queen);
-        assert!(state.castle_availability.black_king);
-        assert!(!state.castle_availability.black_queen);
+        assert!(state.castle_availability.white_king.is_none());
+        assert!(state.castle_availability.white_queen.is_none());
+        assert!(state.castle_availability.black_king.is_some());
+        assert!(state.castle_availability.black_queen.is_none());
     }
 
     #[test]
@@ -2711,12 +3741,12 @@ mod tests {
         let fen_string =
             String::from("r3kbnr/pbqppppp/1pn5/2p5/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1 b kq - 0 6");
         let mut state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(7, 4), &Field(7, 3), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(7, 4), &Field(7, 3), &state);
         state.update_castling_availability(&chess_move);
-        assert!(!state.castle_availability.white_king);
-        assert!(!state.castle_availability.white_queen);
-        assert!(!state.castle_availability.black_king);
-        assert!(!state.castle_availability.black_queen);
+        assert!(state.castle_availability.white_king.is_none());
+        assert!(state.castle_availability.white_queen.is_none());
+        assert!(state.castle_availability.black_king.is_none());
+        assert!(state.castle_availability.black_queen.is_none());
     }
 
     #[test]
@@ -2724,7 +3754,7 @@ mod tests {
         let fen_string =
             String::from("r1b2rk1/pp2ppb1/1qnp1npp/2p5/2B1P3/2N1QN2/PPPP1PPP/R1BR3K w - - 4 10");
         let mut state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(1, 3), &Field(3, 3), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(1, 3), &Field(3, 3), &state);
         state.update_en_passant(&chess_move);
         assert_eq!(state.en_passant, Some(Field(2, 3)));
     }
@@ -2734,7 +3764,7 @@ mod tests {
         let fen_string =
             String::from("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
         let mut state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(6, 2), &Field(4, 2), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(6, 2), &Field(4, 2), &state);
         state.update_en_passant(&chess_move);
         assert_eq!(state.en_passant, Some(Field(5, 2)));
     }
@@ -2744,7 +3774,7 @@ mod tests {
         let fen_string =
             String::from("r1b2rk1/pp2ppb1/1qnp1npp/2p5/2BPP3/2N1QN2/PPP2PPP/R1BR3K b - d3 0 10");
         let mut state = State::load_game_from_fen(fen_string);
-        let chess_move = Move::new(&Field(7, 2), &Field(3, 6), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(7, 2), &Field(3, 6), &state);
         state.update_en_passant(&chess_move);
         assert_eq!(state.en_passant, None);
     }
@@ -2755,19 +3785,11 @@ mod tests {
             String::from("r1bqk2r/pp1pnpbp/2n1p1p1/8/2BPP3/5N2/PP3PPP/RNBQ1RK1 w kq - 1 8");
         let mut state = State::load_game_from_fen(fen_string);
         assert_eq!(state.fullmove_clock, 8);
-        let chess_move = Move::new(&Field(0, 1), &Field(2, 2), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(0, 1), &Field(2, 2), &state);
         state = state.execute_move(&chess_move);
-        state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(state.position_matrix().borrow());
         assert_eq!(state.fullmove_clock, 8);
-        let chess_move = Move::new(&Field(7, 4), &Field(7, 6), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(7, 4), &Field(7, 6), &state);
         state = state.execute_move(&chess_move);
-        state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(state.position_matrix().borrow());
         assert_eq!(state.fullmove_clock, 9);
     }
 
@@ -2777,12 +3799,8 @@ mod tests {
             String::from("r1bq1rk1/pp1pnpbp/2n1p1p1/8/2BPP3/2N2N2/PP3PPP/R1BQ1RK1 w - - 3 9");
         let mut state = State::load_game_from_fen(fen_string);
         assert_eq!(state.halfmove_clock, 3);
-        let chess_move = Move::new(&Field(0, 2), &Field(4, 6), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(0, 2), &Field(4, 6), &state);
         state = state.execute_move(&chess_move);
-        state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(state.position_matrix().borrow());
         assert_eq!(state.halfmove_clock, 4);
     }
 
@@ -2792,12 +3810,8 @@ mod tests {
             String::from("r1bq1rk1/pp1pnpbp/2n1p1p1/6B1/2BPP3/2N2N2/PP3PPP/R2Q1RK1 b - - 4 9");
         let mut state = State::load_game_from_fen(fen_string);
         assert_eq!(state.halfmove_clock, 4);
-        let chess_move = Move::new(&Field(6, 0), &Field(5, 0), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(6, 0), &Field(5, 0), &state);
         state = state.execute_move(&chess_move);
-        state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(state.position_matrix().borrow());
         assert_eq!(state.halfmove_clock, 0);
     }
 
@@ -2807,15 +3821,592 @@ mod tests {
             String::from("r1bqr1k1/3pnpbp/p1n1p1p1/1p4B1/3PP3/P1N2N2/BP3PPP/R2Q1RK1 w - - 2 12");
         let mut state = State::load_game_from_fen(fen_string);
         assert_eq!(state.halfmove_clock, 2);
-        let chess_move = Move::new(&Field(4, 6), &Field(6, 4), state.position_matrix().borrow());
+        let chess_move = Move::new(&Field(4, 6), &Field(6, 4), &state);
         state = state.execute_move(&chess_move);
-        state
-            .position()
-            .borrow_mut()
-            .update_from_matrix(state.position_matrix().borrow());
         assert_eq!(state.halfmove_clock, 0);
     }
 
+    #[test]
+    fn parse_move_input_promotion() {
+        assert_eq!(
+            Move::parse_move_input(String::from("7181q\n")),
+            Ok((Field(6, 0), Field(7, 0), Some(PieceType::Queen)))
+        );
+        assert_eq!(
+            Move::parse_move_input(String::from("2131N")),
+            Ok((Field(1, 0), Field(2, 0), Some(PieceType::Knight)))
+        );
+        assert_eq!(
+            Move::parse_move_input(String::from("7181k")),
+            Err(MoveError::InvalidPromotion)
+        );
+        assert_eq!(
+            Move::parse_move_input(String::from("718")),
+            Err(MoveError::InvalidNumberOfDigits)
+        );
+    }
+
+    #[test]
+    fn move_from_input_castling() {
+        let state = State::new(Some(String::from("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")));
+        for (input, flag) in [
+            ("0-0", MoveFlag::KingsideCastle),
+            ("0-0-0\n", MoveFlag::QueensideCastle),
+            ("O-O", MoveFlag::KingsideCastle),
+        ] {
+            let chess_move = State::move_from_input(String::from(input), &state).unwrap();
+            assert_eq!(chess_move.flag, flag);
+        }
+        // the digits still give the fields of a move
+        let chess_move = State::move_from_input(String::from("1517"), &state).unwrap();
+        assert_eq!(chess_move.flag, MoveFlag::KingsideCastle);
+    }
+
+    #[test]
+    fn promotion_required() {
+        let fen_string = String::from("8/P6k/8/8/8/8/8/K7 w - - 0 1");
+        let state = State::load_game_from_fen(fen_string);
+        let chess_move = Move::new(&Field(6, 0), &Field(7, 0), &state);
+        assert_eq!(
+            state.is_move_legal(&chess_move),
+            Err(MoveError::PromotionRequired)
+        );
+        assert!(state.player_has_legal_move());
+    }
+
+    #[test]
+    fn promotion_invalid() {
+        let fen_string = String::from("8/P6k/8/8/8/8/1P6/K7 w - - 0 1");
+        let state = State::load_game_from_fen(fen_string);
+
+        // pawns can't become kings
+        let mut chess_move =
+            Move::new(&Field(6, 0), &Field(7, 0), &state);
+        chess_move.promotion = Some(PieceType::King);
+        assert_eq!(
+            state.is_move_legal(&chess_move),
+            Err(MoveError::InvalidPromotion)
+        );
+
+        // pawns can only be promoted on the last rank
+        let mut chess_move =
+            Move::new(&Field(1, 1), &Field(2, 1), &state);
+        chess_move.promotion = Some(PieceType::Queen);
+        assert_eq!(
+            state.is_move_legal(&chess_move),
+            Err(MoveError::InvalidPromotion)
+        );
+    }
+
+    #[test]
+    fn promotion_white() {
+        let fen_string = String::from("8/P6k/8/8/8/8/8/K7 w - - 0 1");
+        let state = State::load_game_from_fen(fen_string);
+        let new_state = State::perform_turn_from_input(String::from("7181r"), &state).unwrap();
+        assert_eq!(
+            new_state.position_matrix().0[7][0],
+            Piece {
+                color: Color::White,
+                piecetype: PieceType::Rook
+            }
+        );
+        assert_eq!(new_state.position().0, "R7/7k/8/8/8/8/8/K7");
+    }
+
+    #[test]
+    fn promotion_black_capture() {
+        let fen_string = String::from("7k/8/8/8/8/8/p7/1R4K1 b - - 0 1");
+        let state = State::load_game_from_fen(fen_string);
+        let new_state = State::perform_turn_from_input(String::from("2112n"), &state).unwrap();
+        assert_eq!(
+            new_state.position_matrix().0[0][1],
+            Piece {
+                color: Color::Black,
+                piecetype: PieceType::Knight
+            }
+        );
+        assert_eq!(new_state.position().0, "7k/8/8/8/8/8/8/1n4K1");
+        assert_eq!(new_state.halfmove_clock, 0);
+    }
+
+    fn play_moves(game: &mut GameState, moves: &[&str]) {
+        for move_string in moves {
+            let new_state =
+                State::perform_turn_from_input(String::from(*move_string), game.current_state());
+            assert!(new_state.is_ok(), "Illegal move {}", move_string);
+            handle_state(new_state, game);
+        }
+    }
+
+    // Nf3 Nf6 Ng1 Ng8 brings back the starting position
+    const KNIGHT_SHUFFLE: [&str; 4] = ["1736", "8766", "3617", "6687"];
+
+    #[test]
+    fn threefold_repetition() {
+        let mut game = GameState::new(State::new(None));
+        play_moves(&mut game, &KNIGHT_SHUFFLE);
+        assert_eq!(game.repetition_count(), 2);
+        assert_eq!(game.claimable_draw(), None);
+        assert!(!game.claim_draw());
+
+        play_moves(&mut game, &KNIGHT_SHUFFLE);
+        assert_eq!(game.repetition_count(), 3);
+        assert_eq!(game.claimable_draw(), Some(Termination::ThreefoldRepetition));
+        assert_eq!(game.result(), &None);
+
+        assert!(game.claim_draw());
+        assert_eq!(
+            game.result(),
+            &Some(GameResult::draw(Termination::ThreefoldRepetition))
+        );
+        assert_eq!(game.claimable_draw(), None);
+    }
+
+    #[test]
+    fn fivefold_repetition() {
+        let mut game = GameState::new(State::new(None));
+        for _ in 0..3 {
+            play_moves(&mut game, &KNIGHT_SHUFFLE);
+        }
+        assert_eq!(game.result(), &None);
+        play_moves(&mut game, &KNIGHT_SHUFFLE);
+        assert_eq!(game.repetition_count(), 5);
+        assert_eq!(
+            game.result(),
+            &Some(GameResult::draw(Termination::FivefoldRepetition))
+        );
+
+        // taking back a move leaves the repetition behind
+        assert!(game.undo());
+        assert_eq!(game.repetition_count(), 4);
+        assert_eq!(game.result(), &None);
+        assert!(game.redo());
+        assert_eq!(
+            game.result(),
+            &Some(GameResult::draw(Termination::FivefoldRepetition))
+        );
+    }
+
+    #[test]
+    fn repetition_castle_availability() {
+        // moving the rooks back and forth loses the castling rights,
+        // so the first position is not repeated
+        let fen_string = String::from("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let mut game = GameState::new(State::load_game_from_fen(fen_string));
+        play_moves(&mut game, &["1112", "8182", "1211", "8281"]);
+        assert_eq!(game.repetition_count(), 1);
+        play_moves(&mut game, &["1112", "8182", "1211", "8281"]);
+        assert_eq!(game.repetition_count(), 2);
+    }
+
+    #[test]
+    fn zobrist_key() {
+        // the incremental key matches the key computed from scratch after every move
+        fn check(state: &mut State, depth: usize) {
+            assert_eq!(state.zobrist_key(), zobrist::key(state));
+            if depth == 0 {
+                return;
+            }
+            for chess_move in state.legal_moves() {
+                let key = state.zobrist_key();
+                let undo_info = state.make_move(&chess_move);
+                check(state, depth - 1);
+                state.unmake_move(&chess_move, undo_info);
+                assert_eq!(state.zobrist_key(), key);
+            }
+        }
+        // castling, en passant and promotions (also with captures) on both sides
+        check(
+            &mut State::new(Some(String::from(
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            ))),
+            2,
+        );
+        check(
+            &mut State::new(Some(String::from("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1"))),
+            2,
+        );
+        check(
+            &mut State::new(Some(String::from("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"))),
+            3,
+        );
+        // Chess960 castling where king and rook swap fields
+        check(
+            &mut State::new(Some(String::from("4k3/8/8/8/8/8/8/5KR1 w G - 0 1"))),
+            1,
+        );
+
+        // transpositions have the same key, different positions a different one
+        let mut state = State::new(None);
+        let start = state.zobrist_key();
+        for input in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+            let chess_move = State::move_from_input(String::from(input), &state).unwrap();
+            state = state.execute_move(&chess_move);
+        }
+        assert_eq!(state.zobrist_key(), start);
+        let mut other = State::new(None);
+        for input in ["e4", "e6", "d4"] {
+            let chess_move = State::move_from_input(String::from(input), &other).unwrap();
+            other = other.execute_move(&chess_move);
+        }
+        let transposed = State::new(Some(String::from(
+            "rnbqkbnr/pppp1ppp/4p3/8/3PP3/8/PPP2PPP/RNBQKBNR b KQkq - 0 2",
+        )));
+        assert_eq!(other.zobrist_key(), transposed.zobrist_key());
+        assert_ne!(other.zobrist_key(), start);
+    }
+
+    #[test]
+    fn identity_en_passant() {
+        // en passant is possible, so the field is part of the identity
+        let fen_string =
+            String::from("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+        let state = State::load_game_from_fen(fen_string);
+        assert_eq!(state.identity().en_passant, Some(Field(5, 5)));
+
+        // no pawn can capture en passant, so the field is ignored
+        let fen_string =
+            String::from("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        let state = State::load_game_from_fen(fen_string);
+        assert_eq!(state.identity().en_passant, None);
+    }
+
+    #[test]
+    fn fifty_move_rule() {
+        let fen_string = String::from("8/8/4k3/8/8/3K4/8/R7 w - - 98 80");
+        let mut game = GameState::new(State::load_game_from_fen(fen_string));
+        assert_eq!(game.claimable_draw(), None);
+        play_moves(&mut game, &["1112"]);
+        assert_eq!(game.claimable_draw(), None);
+        play_moves(&mut game, &["6566"]);
+        assert_eq!(game.current_state().halfmove_clock(), 100);
+        assert_eq!(game.claimable_draw(), Some(Termination::FiftyMoveRule));
+        assert_eq!(game.result(), &None);
+        assert!(game.claim_draw());
+        assert_eq!(
+            game.result(),
+            &Some(GameResult::draw(Termination::FiftyMoveRule))
+        );
+    }
+
+    #[test]
+    fn seventy_five_move_rule() {
+        let fen_string = String::from("8/8/4k3/8/8/3K4/8/R7 w - - 149 80");
+        let state = State::load_game_from_fen(fen_string);
+        assert_eq!(state.check_game_over(), None);
+        let state = State::perform_turn_from_input(String::from("1112"), &state).unwrap();
+        assert_eq!(
+            state.check_game_over(),
+            Some(GameResult::draw(Termination::SeventyFiveMoveRule))
+        );
+
+        // checkmate takes precedence
+        let fen_string = String::from("7k/8/6K1/8/8/8/8/R7 w - - 149 80");
+        let state = State::load_game_from_fen(fen_string);
+        let state = State::perform_turn_from_input(String::from("1181"), &state).unwrap();
+        assert_eq!(
+            state.check_game_over(),
+            Some(GameResult::win(&Color::White, Termination::Checkmate))
+        );
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let insufficient = [
+            "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/8/5B2 w - - 0 1",
+            "8/8/4k3/8/8/3K4/8/6N1 b - - 0 1",
+            "8/8/4k3/8/2b5/3K4/8/5B2 w - - 0 1",
+            "8/8/4k3/8/8/3K4/4B3/5B2 w - - 0 1",
+        ];
+        for fen in insufficient.iter() {
+            let state = State::load_game_from_fen(String::from(*fen));
+            assert!(state.board().has_insufficient_material(), "{}", fen);
+            assert_eq!(
+                state.check_game_over(),
+                Some(GameResult::draw(Termination::InsufficientMaterial))
+            );
+        }
+
+        let sufficient = [
+            "8/8/4k3/8/8/3K4/8/R7 w - - 0 1",
+            "8/8/4k3/8/8/3K4/P7/8 w - - 0 1",
+            "8/8/4k3/8/3b4/3K4/8/5B2 w - - 0 1",
+            "8/8/4k3/8/8/3K4/8/5BN1 w - - 0 1",
+            "8/8/4k1n1/8/8/3K4/8/6N1 w - - 0 1",
+        ];
+        for fen in sufficient.iter() {
+            let state = State::load_game_from_fen(String::from(*fen));
+            assert!(!state.board().has_insufficient_material(), "{}", fen);
+            assert_eq!(state.check_game_over(), None);
+        }
+    }
+
+    #[test]
+    fn check_game_over_results() {
+        let state = State::load_game_from_fen(String::from("1Q6/8/8/8/3K4/8/p7/k7 b - - 0 1"));
+        let result = state.check_game_over().unwrap();
+        assert_eq!(result.outcome(), &Outcome::Draw);
+        assert_eq!(result.termination(), &Termination::Stalemate);
+        assert_eq!(result.outcome().score_string(), "1/2-1/2");
+
+        let fen_string = String::from("8/8/4kB1P/PP1p3R/6N1/8/1r6/2r3K1 w - - 0 1");
+        let state = State::load_game_from_fen(fen_string);
+        let result = state.check_game_over().unwrap();
+        assert_eq!(result.outcome(), &Outcome::BlackWon);
+        assert_eq!(result.termination(), &Termination::Checkmate);
+        assert_eq!(result.outcome().score_string(), "0-1");
+    }
+
+    #[test]
+    fn resign_and_agree_draw() {
+        let mut game = GameState::new(State::new(None));
+        play_moves(&mut game, &["2545"]);
+        assert!(game.resign(&Color::Black));
+        assert_eq!(
+            game.result(),
+            &Some(GameResult::win(&Color::White, Termination::Resignation))
+        );
+        assert_eq!(game.result().unwrap().outcome().score_string(), "1-0");
+
+        // no further moves, resignations or draws once the game is over
+        assert!(!game.resign(&Color::White));
+        assert!(!game.agree_draw());
+        let depth = game.tree().depth();
+        play_moves(&mut game, &["7555"]);
+        assert_eq!(game.tree().depth(), depth);
+
+        // taking back the move reopens the game
+        assert!(game.undo());
+        assert_eq!(game.result(), &None);
+        assert!(game.agree_draw());
+        assert_eq!(
+            game.result(),
+            &Some(GameResult::draw(Termination::Agreement))
+        );
+
+        // going forward again restores the resignation
+        assert!(game.redo());
+        assert_eq!(
+            game.result(),
+            &Some(GameResult::win(&Color::White, Termination::Resignation))
+        );
+    }
+
+    fn target_fields(moves: &[Move]) -> Vec<Field> {
+        moves.iter().map(|m| m.target_field().clone()).collect()
+    }
+
+    #[test]
+    fn legal_moves_new_game() {
+        let state = State::new(None);
+        let moves = state.legal_moves();
+        assert_eq!(moves.len(), 20);
+        assert!(moves.iter().all(|m| m.piece().color() == &Color::White));
+        assert_eq!(
+            target_fields(&state.legal_moves_from(&Field(0, 1))),
+            vec![Field(2, 0), Field(2, 2)]
+        );
+        assert!(state.legal_moves_from(&Field(7, 1)).is_empty());
+        assert!(state.legal_moves_from(&Field(4, 4)).is_empty());
+    }
+
+    #[test]
+    fn legal_moves_promotion() {
+        let state = State::load_game_from_fen(String::from("8/P6k/8/8/8/8/8/K7 w - - 0 1"));
+        let moves = state.legal_moves_from(&Field(6, 0));
+        assert_eq!(moves.len(), 4);
+        assert!(moves.iter().all(|m| m.target_field() == &Field(7, 0)));
+        let promotions: Vec<Option<PieceType>> = moves.iter().map(|m| m.promotion).collect();
+        assert_eq!(
+            promotions,
+            vec![
+                Some(PieceType::Queen),
+                Some(PieceType::Rook),
+                Some(PieceType::Bishop),
+                Some(PieceType::Knight)
+            ]
+        );
+        assert_eq!(state.legal_moves().len(), 7);
+    }
+
+    #[test]
+    fn legal_moves_castling_and_en_passant() {
+        let fen_string =
+            String::from("r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4");
+        let state = State::load_game_from_fen(fen_string);
+        let mut targets = target_fields(&state.legal_moves_from(&Field(0, 4)));
+        targets.sort_by_key(|field| (field.0, field.1));
+        assert_eq!(targets, vec![Field(0, 5), Field(0, 6), Field(1, 4)]);
+
+        let fen_string =
+            String::from("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+        let state = State::load_game_from_fen(fen_string);
+        let mut targets = target_fields(&state.legal_moves_from(&Field(4, 4)));
+        targets.sort_by_key(|field| (field.0, field.1));
+        assert_eq!(targets, vec![Field(5, 4), Field(5, 5)]);
+    }
+
+    #[test]
+    fn legal_moves_check() {
+        // only moves resolving the check are legal
+        let fen_string =
+            String::from("r1b1kb1r/pp1Qpppp/1q3n2/8/2B1P3/8/PPP2PPP/RNB1K2R b KQkq - 0 7");
+        let state = State::load_game_from_fen(fen_string);
+        let moves = state.legal_moves();
+        assert_eq!(moves.len(), 3);
+        assert!(moves.iter().all(|m| m.target_field() == &Field(6, 3)));
+    }
+
+    #[test]
+    fn make_and_unmake_move() {
+        // castling, en passant, promotions with and without capture, rook and king moves
+        let fen_strings = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k2r/1P6/8/8/8/8/6p1/R3K2R b KQkq - 3 40",
+        ];
+        for fen in fen_strings.iter() {
+            let mut state = State::load_game_from_fen(String::from(*fen));
+            let original_state = state.clone();
+            for chess_move in original_state.legal_moves() {
+                let undo_info = state.make_move(&chess_move);
+                assert_eq!(state, original_state.execute_move(&chess_move));
+                state.unmake_move(&chess_move, undo_info);
+                assert_eq!(state, original_state, "{:?}", chess_move);
+            }
+        }
+    }
+
+    #[test]
+    fn pawn_push_blocked() {
+        // a piece right in front of the pawn blocks both pushes
+        let fen_string = String::from("4k3/8/8/8/8/4n3/4P3/4K3 w - - 0 1");
+        let state = State::load_game_from_fen(fen_string);
+        assert!(state.legal_moves_from(&Field(1, 4)).is_empty());
+
+        // a piece two fields ahead only blocks the double push
+        let fen_string = String::from("4k3/8/8/8/4n3/8/4P3/4K3 w - - 0 1");
+        let state = State::load_game_from_fen(fen_string);
+        assert_eq!(target_fields(&state.legal_moves_from(&Field(1, 4))), vec![Field(2, 4)]);
+    }
+
+    #[test]
+    fn castling_blocked_or_without_rook() {
+        // a knight between king and rook on the kingside, no rook on the queenside
+        let fen_string = String::from("4k3/8/8/8/8/8/8/4KN1R w K - 0 1");
+        let state = State::load_game_from_fen(fen_string);
+        let chess_move = Move::new(&Field(0, 4), &Field(0, 6), &state);
+        assert_eq!(state.is_move_legal(&chess_move), Err(MoveError::NoPathToTarget));
+        let chess_move = Move::new(&Field(0, 4), &Field(0, 2), &state);
+        assert_eq!(state.is_move_legal(&chess_move), Err(MoveError::CastlingNotAvailable));
+
+        // the queenside castling right can't be given without the rook
+        assert_eq!(
+            State::from_fen("4k3/8/8/8/8/8/8/4KN1R w KQ - 0 1"),
+            Err(FenError::InvalidPosition(PositionError::ImpossibleCastling(Color::White, 0)))
+        );
+    }
+
+    #[test]
+    fn move_flags() {
+        let state = State::load_game_from_fen(String::from(
+            "r3k2r/1P6/8/3pP3/8/8/P7/R3K2R w KQkq d6 0 1",
+        ));
+        let chess_move = Move::new(&Field(1, 0), &Field(3, 0), &state);
+        assert_eq!((chess_move.flag(), chess_move.captured()), (&MoveFlag::DoublePush, &None));
+        let chess_move = Move::new(&Field(4, 4), &Field(5, 3), &state);
+        assert_eq!(chess_move.flag(), &MoveFlag::EnPassant);
+        assert_eq!(chess_move.captured(), &Some(Piece::new(Color::Black, PieceType::Pawn)));
+        assert_eq!(chess_move.captured_field(), Some(Field(4, 3)));
+        let flag = |target_field: Field| *Move::new(&Field(0, 4), &target_field, &state).flag();
+        assert_eq!(flag(Field(0, 6)), MoveFlag::KingsideCastle);
+        assert_eq!(flag(Field(0, 2)), MoveFlag::QueensideCastle);
+        assert_eq!(flag(Field(0, 3)), MoveFlag::Normal);
+
+        // a promotion capturing a rook
+        let chess_move = Move::new(&Field(6, 1), &Field(7, 0), &state);
+        assert_eq!(chess_move.flag(), &MoveFlag::Normal);
+        assert_eq!(chess_move.captured(), &Some(Piece::new(Color::Black, PieceType::Rook)));
+        assert!(chess_move.is_promoting());
+
+        // making and unmaking the moves restores the state
+        for chess_move in state.legal_moves() {
+            let mut new_state = state.clone();
+            let undo_info = new_state.make_move(&chess_move);
+            new_state.unmake_move(&chess_move, undo_info);
+            assert_eq!(new_state, state);
+        }
+    }
+
+    #[test]
+    fn castling_right_lost_when_rook_captured() {
+        let fen_string = String::from("r3k2r/8/8/8/8/8/1B6/4K3 w kq - 0 1");
+        let state = State::load_game_from_fen(fen_string);
+        let chess_move = Move::new(&Field(1, 1), &Field(7, 7), &state);
+        let new_state = state.execute_move(&chess_move);
+        assert!(new_state.castle_availability.black_king.is_none());
+        assert!(new_state.castle_availability.black_queen.is_some());
+    }
+
+    #[test]
+    fn to_san() {
+        let san = |fen: &str, start_field: Field, target_field: Field, promotion: Option<PieceType>| {
+            let state = State::load_game_from_fen(String::from(fen));
+            let mut chess_move = Move::new(&start_field, &target_field, &state);
+            chess_move.promotion = promotion;
+            chess_move.to_san(&state)
+        };
+
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(fen, Field(1, 4), Field(3, 4), None), "e4");
+        assert_eq!(san(fen, Field(0, 6), Field(2, 5), None), "Nf3");
+
+        // captures (including en passant) and disambiguation by file, rank or both
+        let fen = "rn1qkb1r/ppp2ppp/5n2/R2pp3/4P3/8/PPPP1PPP/RNBQKBN1 b Qkq - 0 1";
+        assert_eq!(san(fen, Field(7, 1), Field(6, 3), None), "Nbd7");
+        assert_eq!(san(fen, Field(4, 3), Field(3, 4), None), "dxe4");
+        assert_eq!(san(fen, Field(5, 5), Field(3, 4), None), "Nxe4");
+        let fen = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san(fen, Field(0, 0), Field(2, 0), None), "R1a3");
+        assert_eq!(san(fen, Field(0, 0), Field(0, 1), None), "Rb1");
+        let fen = "k7/8/8/8/8/2Q1Q3/8/2Q1K3 w - - 0 1";
+        assert_eq!(san(fen, Field(2, 2), Field(1, 3), None), "Qc3d2");
+        let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(san(fen, Field(4, 4), Field(5, 3), None), "exd6");
+
+        // promotions, castling, check and checkmate
+        let fen = "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(san(fen, Field(6, 0), Field(7, 1), Some(PieceType::Queen)), "axb8=Q+");
+        assert_eq!(san(fen, Field(6, 0), Field(7, 0), Some(PieceType::Knight)), "a8=N");
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(fen, Field(0, 4), Field(0, 6), None), "O-O");
+        assert_eq!(san(fen, Field(0, 4), Field(0, 2), None), "O-O-O");
+        let fen = "3k4/8/3K4/8/8/8/8/R7 w - - 0 1";
+        assert_eq!(san(fen, Field(0, 0), Field(7, 0), None), "Ra8#");
+        let fen = "3k4/8/1K6/8/8/8/8/R7 w - - 0 1";
+        assert_eq!(san(fen, Field(0, 0), Field(7, 0), None), "Ra8+");
+        let fen = "r4k1r/8/8/8/8/8/8/RK5R w AHah - 0 1";
+        assert_eq!(san(fen, Field(0, 1), Field(0, 0), None), "O-O-O");
+    }
+
+    #[test]
+    fn to_san_round_trip() {
+        // every legal move of 'Kiwipete' is read back as the same move
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let state = State::load_game_from_fen(String::from(fen));
+        for chess_move in state.legal_moves() {
+            assert_eq!(state.parse_san(&chess_move.to_san(&state)), Ok(chess_move));
+        }
+    }
+
+    #[test]
+    fn state_is_send_and_sync() {
+        fn assert_send_and_sync<T: Send + Sync>() {}
+        assert_send_and_sync::<State>();
+        assert_send_and_sync::<GameState>();
+    }
+
     // M1
     // 8/8/4kB1P/PP1p3R/6N1/2r5/1r6/6K1 b - - 0 1
 
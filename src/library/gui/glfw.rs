@@ -13,6 +13,20 @@ pub const GLFW_MOUSE_BUTTON_RIGHT: c_int = 1;
 pub const GLFW_RELEASE: c_int = 0;
 pub const GLFW_PRESS: c_int = 1;
 pub const GLFW_REPEAT: c_int = 2;
+pub const GLFW_KEY_A: c_int = 65;
+pub const GLFW_KEY_B: c_int = 66;
+pub const GLFW_KEY_C: c_int = 67;
+pub const GLFW_KEY_D: c_int = 68;
+pub const GLFW_KEY_F: c_int = 70;
+pub const GLFW_KEY_N: c_int = 78;
+pub const GLFW_KEY_P: c_int = 80;
+pub const GLFW_KEY_Q: c_int = 81;
+pub const GLFW_KEY_R: c_int = 82;
+pub const GLFW_KEY_S: c_int = 83;
+pub const GLFW_KEY_X: c_int = 88;
+pub const GLFW_KEY_DELETE: c_int = 261;
+pub const GLFW_KEY_DOWN: c_int = 264;
+pub const GLFW_KEY_UP: c_int = 265;
 
 #[link(name = "glfw3", kind = "static")]
 #[link(name = "user32")]
@@ -38,6 +52,10 @@ extern "C" {
         window: *const GLFWwindow,
         callback: unsafe extern "C" fn(*const GLFWwindow, c_int, c_int, c_int),
     );
+    pub fn glfwSetKeyCallback(
+        window: *const GLFWwindow,
+        callback: unsafe extern "C" fn(*const GLFWwindow, c_int, c_int, c_int, c_int),
+    );
     pub fn glfwGetCursorPos(window: *const GLFWwindow, xpos: *mut f64, ypos: *mut f64);
     pub fn glfwSetWindowUserPointer(window: *const GLFWwindow, pointer: *const c_void);
     pub fn glfwGetWindowUserPointer(window: *const GLFWwindow) -> *const c_void;
@@ -14,37 +14,30 @@ use crate::library::{
     gui::{glfw::*, renderer::*, utils::print_opengl_version},
 };
 
-pub unsafe fn run(fen: Option<String>) {
-    let mut renderer: Renderer = Renderer::init(fen);
-    let mut game_active = true;
+pub unsafe fn run(game: GameState) {
+    let mut renderer: Renderer = Renderer::init(game);
+    let mut announced_result: Option<GameResult> = None;
 
     print_opengl_version(&renderer.gl);
     //print_opengl_extensions(&renderer.gl);
 
     glfwSetMouseButtonCallback(renderer.get_window(), click_callback);
+    glfwSetKeyCallback(renderer.get_window(), key_callback);
     glfwSetFramebufferSizeCallback(renderer.get_window(), framebuffer_size_callback);
 
     while glfwWindowShouldClose(renderer.get_window()) == 0 {
-        if game_active {
-            game_active = false;
-            match renderer.check_game_over() {
-                GameOver::BlackWon => {
-                    println!("Checkmate, black won!");
-                }
-                GameOver::WhiteWon => {
-                    println!("Checkmate, white won!");
-                }
-                GameOver::Stalemate => {
-                    println!("Stalemate!");
-                }
-                _ => {
-                    game_active = true;
-                }
+        // announce the result once, the game may still be reopened by taking back moves
+        let result = renderer.game_result();
+        if let Some(game_result) = result {
+            if result != announced_result {
+                println!("{}", game_result);
             }
-            renderer.clear();
-            renderer.draw();
-            glfwSwapBuffers(renderer.get_window());
         }
+        announced_result = result;
+
+        renderer.clear();
+        renderer.draw();
+        glfwSwapBuffers(renderer.get_window());
 
         glfwWaitEvents();
         //glfwPollEvents();
@@ -1,13 +1,13 @@
 use crate::gl;
-use crate::library::game::*;
+use crate::library::{game::*, pgn::PgnGame};
 use crate::library::gui::{
     glfw::*, index_buffer::*, maths::*, opengl::*, shader::*, texture::*, utils::*,
     vertex_array::*, vertex_buffer::*,
 };
 use libc::{c_int, c_void};
 use std::{
-    collections::LinkedList,
     ffi::CString,
+    fs,
     mem::forget,
     ptr::null_mut,
     rc::Rc,
@@ -50,7 +50,7 @@ pub struct Glfw {
 }
 
 impl Renderer {
-    pub unsafe fn init(fen: Option<String>) -> Renderer {
+    pub unsafe fn init(game: GameState) -> Renderer {
         let glfw = Renderer::init_glfw().expect("Failed to initialize GLFW");
         let gl = Rc::new(GL::bind());
 
@@ -100,13 +100,7 @@ impl Renderer {
         Renderer::set_blend_func(Rc::clone(&gl));
 
         // "bind" the game state to the glfw window
-        let mut active_states: LinkedList<State> = LinkedList::new();
-        active_states.push_back(State::new(fen));
-        let game_state = Arc::new(RwLock::new(GameState {
-            selected_field: None,
-            active_states,
-            inactive_states: LinkedList::new(),
-        }));
+        let game_state = Arc::new(RwLock::new(game));
 
         glfwSetWindowUserPointer(glfw.window, Arc::as_ptr(&game_state) as *const c_void);
 
@@ -159,11 +153,11 @@ impl Renderer {
         self.glfw.window
     }
 
-    pub fn check_game_over(&self) -> GameOver {
+    pub fn game_result(&self) -> Option<GameResult> {
         let game_state = self.game_state.read().unwrap();
-        let game_over = game_state.active_states.back().unwrap().check_game_over();
+        let result = *game_state.result();
         drop(game_state);
-        game_over
+        result
     }
 
     pub fn clear(&self) {
@@ -212,16 +206,12 @@ impl Renderer {
     fn bind_board_state(&self) {
         self.v_buffer.bind();
         let game_state = self.game_state.read().unwrap();
-        let b_verts = Renderer::get_board_vertices(&game_state.selected_field);
+        let legal_targets = Renderer::get_legal_targets(&game_state);
+        let b_verts = Renderer::get_board_vertices(&game_state.selected_field, &legal_targets);
         self.v_buffer.buffer_sub_data(&b_verts, b_verts.len(), 0);
         unsafe {
-            let pos_matrix = game_state
-                .active_states
-                .back()
-                .unwrap()
-                .position_matrix()
-                .borrow();
-            let (p_verts, p_inds) = Renderer::get_piece_vertices_and_indices(pos_matrix);
+            let pos_matrix = game_state.current_state().position_matrix();
+            let (p_verts, p_inds) = Renderer::get_piece_vertices_and_indices(&pos_matrix);
             self.v_buffer.buffer_sub_data(&p_verts, p_verts.len(), 324);
             self.i_buffer.bind();
             self.i_buffer.buffer_sub_data(&p_inds, p_inds.len(), 384);
@@ -239,7 +229,23 @@ impl Renderer {
         gl!(gl.enable(GL_BLEND));
     }
 
-    pub fn get_board_vertices(selected_field: &Option<(usize, usize)>) -> Vec<f32> {
+    // the fields the selected piece can legally move to (in the same coordinates as the selected field)
+    fn get_legal_targets(game_state: &GameState) -> Vec<(usize, usize)> {
+        match game_state.selected_field {
+            Some((x, y)) if !game_state.is_over() => game_state
+                .current_state()
+                .legal_moves_from(&Field(y, x))
+                .iter()
+                .map(|chess_move| (chess_move.target_field().1, chess_move.target_field().0))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn get_board_vertices(
+        selected_field: &Option<(usize, usize)>,
+        legal_targets: &[(usize, usize)],
+    ) -> Vec<f32> {
         let mut vertices = Vec::new();
         for i in 0..=8 {
             for j in 0..=8 {
@@ -256,6 +262,13 @@ impl Renderer {
             }
         };
 
+        for (x, y) in legal_targets {
+            let target_field_indices = Renderer::get_board_indices_for_field(*x, *y);
+            for index in target_field_indices {
+                vertices[index].texture_id = 10.0;
+            }
+        }
+
         Renderer::deserialize(vertices)
     }
 
@@ -367,7 +380,7 @@ impl Renderer {
     }
 
     unsafe fn get_piece_vertices_and_indices(
-        position_matrix: Ref<PositionMatrix>,
+        position_matrix: &PositionMatrix,
     ) -> (Vec<f32>, Vec<u32>) {
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
@@ -504,6 +517,34 @@ pub unsafe extern "C" fn click_callback(
     }
 }
 
+pub(crate) unsafe extern "C" fn key_callback(
+    window: *const GLFWwindow,
+    key: c_int,
+    _scancode: c_int,
+    action: c_int,
+    _mods: c_int,
+) {
+    if action != GLFW_PRESS {
+        return;
+    }
+    match key {
+        GLFW_KEY_Q => promote_pawn(glfwGetWindowUserPointer(window), 'q'),
+        GLFW_KEY_R => promote_pawn(glfwGetWindowUserPointer(window), 'r'),
+        GLFW_KEY_B => promote_pawn(glfwGetWindowUserPointer(window), 'b'),
+        GLFW_KEY_N => promote_pawn(glfwGetWindowUserPointer(window), 'n'),
+        GLFW_KEY_C => claim_draw(glfwGetWindowUserPointer(window)),
+        GLFW_KEY_X => resign(glfwGetWindowUserPointer(window)),
+        GLFW_KEY_D => offer_draw(glfwGetWindowUserPointer(window)),
+        GLFW_KEY_A => accept_draw(glfwGetWindowUserPointer(window)),
+        GLFW_KEY_F => print_fen(glfwGetWindowUserPointer(window)),
+        GLFW_KEY_S => save_pgn(glfwGetWindowUserPointer(window)),
+        GLFW_KEY_UP | GLFW_KEY_DOWN | GLFW_KEY_P | GLFW_KEY_DELETE => {
+            edit_variation(glfwGetWindowUserPointer(window), key)
+        }
+        _ => {}
+    }
+}
+
 pub extern "C" fn window_size_callback(_window: *const GLFWwindow, width: c_int, height: c_int) {
     unsafe {
         WIDTH = width as f32;
@@ -526,20 +567,29 @@ pub unsafe fn scroll_state(game_state_ptr: *const c_void, button: UiElement) {
     let game_state_arc = Arc::from_raw(game_state_ptr as *const RwLock<GameState>);
     let mut game_state = game_state_arc.write().unwrap();
     match button {
-        UiElement::BackwardButton => {
-            if game_state.active_states.len() > 1 {
-                let last_active_state = game_state.active_states.pop_back().unwrap();
-                game_state.inactive_states.push_back(last_active_state);
-            }
-        }
-        UiElement::ForwardButton => {
-            if game_state.inactive_states.len() > 0 {
-                let last_inactive_state = game_state.inactive_states.pop_back().unwrap();
-                game_state.active_states.push_back(last_inactive_state);
-            }
-        }
+        UiElement::BackwardButton => game_state.undo(),
+        UiElement::ForwardButton => game_state.redo(),
         _ => panic!("There should only be forward and backward buttons at this point."),
     };
+    announce_claimable_draw(&game_state);
+    drop(game_state);
+    forget(game_state_arc);
+}
+
+// up / down switch between the variations, P promotes the current line and delete removes the last move
+unsafe fn edit_variation(pointer: *const c_void, key: c_int) {
+    let game_state_arc = Arc::from_raw(pointer as *const RwLock<GameState>);
+    let mut game_state = game_state_arc.write().unwrap();
+    let changed = match key {
+        GLFW_KEY_UP => game_state.previous_variation(),
+        GLFW_KEY_DOWN => game_state.next_variation(),
+        GLFW_KEY_P => game_state.promote_variation(),
+        GLFW_KEY_DELETE => game_state.delete_variation(),
+        _ => panic!("There should only be variation keys at this point."),
+    };
+    if changed {
+        announce_claimable_draw(&game_state);
+    }
     drop(game_state);
     forget(game_state_arc);
 }
@@ -555,10 +605,15 @@ pub unsafe fn toggle_field(pointer: *const c_void, value: (usize, usize)) {
             move_string.push(char::from_digit(inner.0 as u32 + 1, 10).unwrap());
             move_string.push(char::from_digit(value.1 as u32 + 1, 10).unwrap());
             move_string.push(char::from_digit(value.0 as u32 + 1, 10).unwrap());
-            let current_state = game_state.active_states.back().unwrap();
-            let new_state = State::perform_turn_from_input(move_string, current_state);
-            drop(current_state);
-            handle_state(new_state, &mut game_state);
+            let current_state = game_state.current_state();
+            match State::perform_turn_from_input(move_string.clone(), current_state) {
+                Err(MoveError::PromotionRequired) => {
+                    println!("Promote to (q)ueen, (r)ook, (b)ishop or k(n)ight? Press the key of the piece.");
+                    game_state.pending_promotion = Some(move_string);
+                }
+                new_state => handle_state(new_state, &mut game_state),
+            }
+            announce_claimable_draw(&game_state);
             None
         }
         _ => Some(value),
@@ -568,8 +623,103 @@ pub unsafe fn toggle_field(pointer: *const c_void, value: (usize, usize)) {
     forget(game_state_arc);
 }
 
+unsafe fn promote_pawn(pointer: *const c_void, piece: char) {
+    let game_state_arc = Arc::from_raw(pointer as *const RwLock<GameState>);
+    let mut game_state = game_state_arc.write().unwrap();
+    if let Some(mut move_string) = game_state.pending_promotion.take() {
+        move_string.push(piece);
+        let current_state = game_state.current_state();
+        let new_state = State::perform_turn_from_input(move_string, current_state);
+        handle_state(new_state, &mut game_state);
+        announce_claimable_draw(&game_state);
+    }
+    drop(game_state);
+    forget(game_state_arc);
+}
+
+unsafe fn claim_draw(pointer: *const c_void) {
+    let game_state_arc = Arc::from_raw(pointer as *const RwLock<GameState>);
+    let mut game_state = game_state_arc.write().unwrap();
+    if !game_state.claim_draw() {
+        println!("There is no draw to claim!");
+    }
+    drop(game_state);
+    forget(game_state_arc);
+}
+
+unsafe fn print_fen(pointer: *const c_void) {
+    let game_state_arc = Arc::from_raw(pointer as *const RwLock<GameState>);
+    let game_state = game_state_arc.read().unwrap();
+    println!("{}", game_state.current_state().to_fen());
+    drop(game_state);
+    forget(game_state_arc);
+}
+
+// the game is saved to 'game.pgn' in the working directory
+unsafe fn save_pgn(pointer: *const c_void) {
+    let game_state_arc = Arc::from_raw(pointer as *const RwLock<GameState>);
+    let game_state = game_state_arc.read().unwrap();
+    match fs::write("game.pgn", PgnGame::from_game_state(&game_state).to_pgn()) {
+        Ok(_) => println!("The game was saved to game.pgn."),
+        Err(e) => println!("The game couldn't be saved: {}", e),
+    }
+    drop(game_state);
+    forget(game_state_arc);
+}
+
+unsafe fn resign(pointer: *const c_void) {
+    let game_state_arc = Arc::from_raw(pointer as *const RwLock<GameState>);
+    let mut game_state = game_state_arc.write().unwrap();
+    let player = *game_state.current_state().turn();
+    game_state.resign(&player);
+    drop(game_state);
+    forget(game_state_arc);
+}
+
+unsafe fn offer_draw(pointer: *const c_void) {
+    let game_state_arc = Arc::from_raw(pointer as *const RwLock<GameState>);
+    let mut game_state = game_state_arc.write().unwrap();
+    if !game_state.is_over() {
+        game_state.draw_offered = true;
+        match game_state.current_state().turn() {
+            Color::White => println!("White offers a draw, press A to accept."),
+            Color::Black => println!("Black offers a draw, press A to accept."),
+            _ => {}
+        }
+    }
+    drop(game_state);
+    forget(game_state_arc);
+}
+
+unsafe fn accept_draw(pointer: *const c_void) {
+    let game_state_arc = Arc::from_raw(pointer as *const RwLock<GameState>);
+    let mut game_state = game_state_arc.write().unwrap();
+    if game_state.draw_offered {
+        game_state.agree_draw();
+    } else {
+        println!("There is no draw offer to accept!");
+    }
+    drop(game_state);
+    forget(game_state_arc);
+}
+
+fn announce_claimable_draw(game_state: &GameState) {
+    match game_state.claimable_draw() {
+        Some(Termination::ThreefoldRepetition) => {
+            println!("The position occurred three times, press C to claim a draw.")
+        }
+        Some(Termination::FiftyMoveRule) => {
+            println!("Fifty moves without a capture or pawn move, press C to claim a draw.")
+        }
+        _ => {}
+    }
+}
+
 pub unsafe fn deselected_field(pointer: *const c_void) {
-    let selected_field_arc = Arc::from_raw(pointer as *const RwLock<Option<(usize, usize)>>);
-    *selected_field_arc.write().unwrap() = None;
-    forget(selected_field_arc);
+    let game_state_arc = Arc::from_raw(pointer as *const RwLock<GameState>);
+    let mut game_state = game_state_arc.write().unwrap();
+    game_state.selected_field = None;
+    game_state.pending_promotion = None;
+    drop(game_state);
+    forget(game_state_arc);
 }
@@ -43,8 +43,12 @@ void main()
     {
         o_Color = texture(u_Textures[index], v_TexCoord);
     }
-    else
+    else if (index == 9)
     {
         o_Color = vec4(0.9, 0.2, 0.15, 0.85);
     }
+    else
+    {
+        o_Color = vec4(0.3, 0.7, 0.35, 0.85);
+    }
 }
\ No newline at end of file
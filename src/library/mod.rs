@@ -1,4 +1,16 @@
+pub mod attacks;
+pub mod bitboard;
+pub mod chess960;
 pub mod cli;
 pub mod config;
+pub mod epd;
 pub mod game;
 pub mod gui;
+pub mod perft;
+pub mod pgn;
+pub mod san;
+#[cfg(feature = "serde")]
+pub mod serialization;
+pub mod tree;
+pub mod uci;
+pub mod zobrist;
@@ -0,0 +1,138 @@
+use crate::library::game::{Move, State};
+
+/*
+    Perft ("performance test") walks the tree of legal moves up to a given depth and counts its leaf nodes.
+
+    Comparing the counts to the known values of well-studied positions is the standard way to verify a move generator,
+    since even the rarest special moves (castling, en-passant, promotions, pins, ...) show up in the numbers. Divide
+    lists the count for each move of the root position, which narrows a wrong total down to the faulty move.
+*/
+impl State {
+    pub fn perft(&self, depth: usize) -> u64 {
+        let mut state = self.clone();
+        state.count_nodes(depth)
+    }
+
+    pub fn divide(&self, depth: usize) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+
+        let mut state = self.clone();
+        let mut result = Vec::new();
+        for chess_move in self.legal_moves() {
+            let undo_info = state.make_move(&chess_move);
+            let nodes = state.count_nodes(depth - 1);
+            state.unmake_move(&chess_move, undo_info);
+            result.push((chess_move, nodes));
+        }
+        result
+    }
+
+    fn count_nodes(&mut self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64; // no need to play the moves of the last level
+        }
+
+        let mut nodes = 0;
+        for chess_move in moves {
+            let undo_info = self.make_move(&chess_move);
+            nodes += self.count_nodes(depth - 1);
+            self.unmake_move(&chess_move, undo_info);
+        }
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // positions and node counts from https://www.chessprogramming.org/Perft_Results
+    fn assert_perft(fen: &str, node_counts: &[u64]) {
+        let state = State::load_game_from_fen(String::from(fen));
+        for (depth, nodes) in node_counts.iter().enumerate() {
+            assert_eq!(
+                state.perft(depth + 1),
+                *nodes,
+                "{} (depth {})",
+                fen,
+                depth + 1
+            );
+        }
+    }
+
+    #[test]
+    fn perft_depth_zero() {
+        let state = State::new(None);
+        assert_eq!(state.perft(0), 1);
+        assert!(state.divide(0).is_empty());
+    }
+
+    #[test]
+    fn perft_start_position() {
+        assert_perft(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &[20, 400, 8902, 197281],
+        );
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97862],
+        );
+    }
+
+    #[test]
+    fn perft_position_3() {
+        assert_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2812, 43238],
+        );
+    }
+
+    #[test]
+    fn perft_position_4() {
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        );
+        // the same position mirrored with colors reversed
+        assert_perft(
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            &[6, 264, 9467],
+        );
+    }
+
+    #[test]
+    fn perft_position_5() {
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486, 62379],
+        );
+    }
+
+    #[test]
+    fn perft_position_6() {
+        assert_perft(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            &[46, 2079, 89890],
+        );
+    }
+
+    #[test]
+    fn divide_start_position() {
+        let state = State::new(None);
+        let result = state.divide(3);
+        assert_eq!(result.len(), 20);
+        assert_eq!(result.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+    }
+}
@@ -0,0 +1,1031 @@
+use crate::library::game::{
+    Color, FenError, GameResult, GameState, Move, Outcome, State, Termination,
+};
+use crate::library::tree::{Annotation, GameNode, GameTree};
+use std::{error::Error, fmt, iter::Peekable, vec::IntoIter};
+
+const LINE_LENGTH: usize = 80;
+
+/*
+    Portable Game Notation (PGN) is the common format to store chess games. A game consists of tag pairs like
+    [White "Fischer, Robert J."] followed by the movetext, e.g.:
+
+        1. e4 e5 2. Nf3 {the most common move} Nc6 (2... d6 $6 3. d4) 3. Bb5 a6 1-0
+
+    The moves are given in SAN and may be followed by comments ("{...}" or ";" up to the end of the line),
+    numeric annotation glyphs ("$1" or the equivalent "!", "?", "!!", "??", "!?", "?!") and variations, which are
+    alternatives to the move they follow and may contain variations themselves. The game ends with its result.
+
+    Games starting from a custom position give it in the tags "SetUp" and "FEN". All moves are checked while reading
+    a game, so the game can be replayed right away.
+
+    Games are written in the export format: the seven tag roster (Event, Site, Date, Round, White, Black, Result)
+    comes first and the movetext is wrapped at 80 columns.
+*/
+#[derive(PartialEq, Clone, Debug)]
+pub struct PgnGame {
+    tags: Vec<(String, String)>,
+    moves: Vec<PgnMove>,
+    result: Option<Outcome>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct PgnMove {
+    chess_move: Move,
+    nags: Vec<u8>,
+    comment_before: Option<String>,
+    comment_after: Option<String>,
+    variations: Vec<Vec<PgnMove>>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum PgnError {
+    NoGame,
+    InvalidTag(String),
+    UnterminatedComment,
+    UnbalancedVariation,
+    UnexpectedToken(String),
+    InvalidFen(FenError),
+    IllegalMove(String),
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::NoGame => write!(f, "Invalid PGN, no game found"),
+            PgnError::InvalidTag(tag) => write!(f, "Invalid PGN, can't read the tag '{}'", tag),
+            PgnError::UnterminatedComment => {
+                write!(f, "Invalid PGN, a comment is missing its '}}'")
+            }
+            PgnError::UnbalancedVariation => {
+                write!(
+                    f,
+                    "Invalid PGN, the parentheses of the variations don't match"
+                )
+            }
+            PgnError::UnexpectedToken(token) => write!(f, "Invalid PGN, unexpected '{}'", token),
+            PgnError::InvalidFen(e) => write!(f, "Invalid PGN, the FEN tag is wrong: {}", e),
+            PgnError::IllegalMove(chess_move) => {
+                write!(f, "Invalid PGN, the move {} can't be played", chess_move)
+            }
+        }
+    }
+}
+
+impl Error for PgnError {}
+
+#[derive(PartialEq, Clone, Debug)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    VariationStart,
+    VariationEnd,
+    San(String),
+    Result(Option<Outcome>),
+}
+
+impl PgnGame {
+    // reads all games of a PGN file
+    pub fn from_pgn(pgn: &str) -> Result<Vec<PgnGame>, PgnError> {
+        let mut tokens = match PgnGame::tokenize(pgn) {
+            Ok(tokens) => tokens.into_iter().peekable(),
+            Err(e) => return Err(e),
+        };
+
+        let mut games = Vec::new();
+        while tokens.peek().is_some() {
+            match PgnGame::read_game(&mut tokens) {
+                Ok(game) => games.push(game),
+                Err(e) => return Err(e),
+            }
+        }
+
+        match games.is_empty() {
+            true => Err(PgnError::NoGame),
+            false => Ok(games),
+        }
+    }
+
+    pub fn tags(&self) -> &Vec<(String, String)> {
+        &self.tags
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // the mainline of the game, including the variations branching off it
+    pub fn moves(&self) -> &Vec<PgnMove> {
+        &self.moves
+    }
+
+    // the result of the game, if it is over
+    pub fn result(&self) -> &Option<Outcome> {
+        &self.result
+    }
+
+    // the position the game started from
+    pub fn initial_state(&self) -> State {
+        match PgnGame::initial_state_from_tags(&self.tags) {
+            Ok(state) => state,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // the positions of the mainline, starting with the initial one
+    pub fn states(&self) -> Vec<State> {
+        let mut state = self.initial_state();
+        let mut states = vec![state.clone()];
+        for pgn_move in self.moves.iter() {
+            state.make_move(&pgn_move.chess_move);
+            states.push(state.clone());
+        }
+        states
+    }
+
+    // the game as it is played in the CLI and GUI (including the variations), positioned after the last move.
+    // the tags and annotations are kept, and a finished game can't be continued.
+    pub fn game_state(&self) -> GameState {
+        let mut tree = GameTree::new(self.initial_state());
+        PgnGame::add_to_tree(&mut tree, &self.moves);
+        if tree.current_state().check_game_over().is_none() {
+            tree.set_result(self.declared_result());
+        }
+        let mut game = GameState::from_tree(tree);
+        game.set_tags(self.tags.clone());
+        game
+    }
+
+    // the result of a game that ended without its final position deciding it, e.g. by resignation
+    fn declared_result(&self) -> Option<GameResult> {
+        let outcome = self.result?;
+        let termination = match self.tag("Termination") {
+            Some(termination) if termination.eq_ignore_ascii_case("time forfeit") => {
+                Termination::TimeForfeit
+            }
+            _ if outcome == Outcome::Draw => Termination::Agreement,
+            _ => Termination::Resignation,
+        };
+        Some(GameResult::new(outcome, termination))
+    }
+
+    // the game played in the CLI or GUI with all of its variations and the tags it was loaded with
+    pub fn from_game_state(game: &GameState) -> PgnGame {
+        let tree = game.tree();
+        let initial_state = tree.root().state();
+
+        // the result of the game is the one at the end of its mainline
+        let result = if tree.is_mainline() && tree.current().children().is_empty() {
+            game.result().map(|result| *result.outcome())
+        } else {
+            let end = tree.mainline_end();
+            match end.state().check_game_over() {
+                Some(result) => Some(*result.outcome()),
+                None => end.result().map(|result| *result.outcome()),
+            }
+        };
+        let result_tag = match result {
+            Some(outcome) => outcome.score_string(),
+            None => String::from("*"),
+        };
+
+        // the seven tag roster comes first, missing tags are unknown ("?")
+        let roster = [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", &result_tag),
+        ];
+        let game_tag = |name: &str| {
+            game.tags()
+                .iter()
+                .find(|(tag_name, _)| tag_name == name)
+                .map(|(_, value)| value.clone())
+        };
+        let mut tags: Vec<(String, String)> = roster
+            .iter()
+            .map(|(name, default)| match game_tag(name) {
+                Some(value) if *name != "Result" => (String::from(*name), value),
+                _ => (String::from(*name), String::from(*default)),
+            })
+            .collect();
+        tags.extend(
+            game.tags()
+                .iter()
+                .filter(|(name, _)| roster.iter().all(|(roster_name, _)| name != roster_name))
+                .cloned(),
+        );
+
+        // a custom start position needs to be given as well
+        if initial_state.is_chess960() && game_tag("Variant").is_none() {
+            tags.push((String::from("Variant"), String::from("Chess960")));
+        }
+        if initial_state.is_chess960() || initial_state != &State::new(None) {
+            PgnGame::set_tag(&mut tags, "SetUp", String::from("1"));
+            PgnGame::set_tag(&mut tags, "FEN", initial_state.to_fen());
+        }
+
+        let moves = PgnGame::moves_from_tree(tree.root());
+        PgnGame {
+            tags,
+            moves,
+            result,
+        }
+    }
+
+    fn set_tag(tags: &mut Vec<(String, String)>, name: &str, value: String) {
+        match tags.iter_mut().find(|(tag_name, _)| tag_name == name) {
+            Some(tag) => tag.1 = value,
+            None => tags.push((String::from(name), value)),
+        }
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for (name, value) in self.tags.iter() {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::new();
+        PgnGame::write_moves(&self.moves, self.initial_state(), &mut tokens);
+        tokens.push(match self.result {
+            Some(outcome) => outcome.score_string(),
+            None => String::from("*"),
+        });
+
+        // the tokens are wrapped, so that no line is longer than 80 characters
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_LENGTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push_str("\n\n");
+        pgn
+    }
+
+    // the SAN of the moves (with move numbers, annotations, comments and variations) as separate tokens
+    fn write_moves(moves: &[PgnMove], mut state: State, tokens: &mut Vec<String>) {
+        // black's moves are numbered at the start and after comments or variations
+        let mut needs_number = true;
+
+        for pgn_move in moves {
+            if let Some(comment) = &pgn_move.comment_before {
+                PgnGame::write_comment(comment, tokens);
+                needs_number = true;
+            }
+
+            match state.turn() {
+                Color::White => tokens.push(format!("{}.", state.fullmove_clock())),
+                _ if needs_number => tokens.push(format!("{}...", state.fullmove_clock())),
+                _ => {}
+            }
+            tokens.push(pgn_move.chess_move.to_san(&state));
+            needs_number = false;
+
+            for nag in pgn_move.nags.iter() {
+                tokens.push(format!("${}", nag));
+            }
+            if let Some(comment) = &pgn_move.comment_after {
+                PgnGame::write_comment(comment, tokens);
+                needs_number = true;
+            }
+
+            for variation in pgn_move.variations.iter() {
+                let mut variation_tokens = Vec::new();
+                PgnGame::write_moves(variation, state.clone(), &mut variation_tokens);
+                if let Some(first) = variation_tokens.first_mut() {
+                    first.insert(0, '(');
+                }
+                if let Some(last) = variation_tokens.last_mut() {
+                    last.push(')');
+                }
+                tokens.append(&mut variation_tokens);
+                needs_number = true;
+            }
+
+            state.make_move(&pgn_move.chess_move);
+        }
+    }
+
+    // comments are split into words, so that they can be wrapped as well
+    fn write_comment(comment: &str, tokens: &mut Vec<String>) {
+        let mut words: Vec<String> = comment.split_whitespace().map(String::from).collect();
+        if words.is_empty() {
+            words.push(String::new());
+        }
+        words.first_mut().unwrap().insert(0, '{');
+        words.last_mut().unwrap().push('}');
+        tokens.append(&mut words);
+    }
+
+    // plays the moves (the mainline after each of its variations) from the current node of the tree
+    fn add_to_tree(tree: &mut GameTree, moves: &[PgnMove]) {
+        for pgn_move in moves.iter() {
+            let mut state = tree.current_state().clone();
+            state.make_move(&pgn_move.chess_move);
+            if !pgn_move.variations.is_empty() {
+                // the mainline move has to be played first so that it stays the first child
+                tree.push(state.clone());
+                tree.back();
+                for variation in pgn_move.variations.iter() {
+                    PgnGame::add_to_tree(tree, variation);
+                    for _ in 0..variation.len() {
+                        tree.back();
+                    }
+                }
+            }
+            tree.push(state);
+            tree.annotate(pgn_move.annotation());
+        }
+    }
+
+    // the line following the first children of the node, with the other children as variations
+    fn moves_from_tree(node: &GameNode) -> Vec<PgnMove> {
+        let mut moves = Vec::new();
+        let mut node = node;
+        while let Some(next_node) = node.children().first() {
+            let variations = node.children()[1..]
+                .iter()
+                .map(|variation_node| {
+                    let mut variation = vec![PgnGame::tree_move(node, variation_node)];
+                    variation.extend(PgnGame::moves_from_tree(variation_node));
+                    variation
+                })
+                .collect();
+            let mut pgn_move = PgnGame::tree_move(node, next_node);
+            pgn_move.variations = variations;
+            moves.push(pgn_move);
+            node = next_node;
+        }
+        moves
+    }
+
+    fn tree_move(node: &GameNode, next_node: &GameNode) -> PgnMove {
+        let annotation = next_node.annotation();
+        PgnMove {
+            chess_move: PgnGame::move_between(node.state(), next_node.state()),
+            nags: annotation.nags.clone(),
+            comment_before: annotation.comment_before.clone(),
+            comment_after: annotation.comment_after.clone(),
+            variations: Vec::new(),
+        }
+    }
+
+    fn move_between(state: &State, next_state: &State) -> Move {
+        let mut moves = state.legal_moves().into_iter().filter(|chess_move| {
+            let mut new_state = state.clone();
+            new_state.make_move(chess_move);
+            &new_state == next_state
+        });
+        match moves.next() {
+            Some(chess_move) => chess_move,
+            None => panic!("Corrupt game state - no move leads to the next position!"),
+        }
+    }
+
+    fn read_game(tokens: &mut Peekable<IntoIter<Token>>) -> Result<PgnGame, PgnError> {
+        let mut tags = Vec::new();
+        while let Some(Token::Tag(_, _)) = tokens.peek() {
+            if let Some(Token::Tag(name, value)) = tokens.next() {
+                tags.push((name, value));
+            }
+        }
+
+        let state = PgnGame::initial_state_from_tags(&tags)?;
+        let (moves, result) = PgnGame::read_moves(tokens, state, false)?;
+
+        // without a result token the result tag is used
+        let result = match result {
+            Some(result) => result,
+            None => match tags.iter().find(|(name, _)| name == "Result") {
+                Some((_, value)) => PgnGame::outcome(value),
+                None => None,
+            },
+        };
+
+        Ok(PgnGame {
+            tags,
+            moves,
+            result,
+        })
+    }
+
+    fn initial_state_from_tags(tags: &[(String, String)]) -> Result<State, PgnError> {
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(tag_name, _)| tag_name == name)
+                .map(|(_, value)| value)
+        };
+
+        let mut state = match tag("FEN") {
+            Some(fen) => match State::from_fen(fen) {
+                Ok(state) => state,
+                Err(e) => return Err(PgnError::InvalidFen(e)),
+            },
+            None => State::new(None),
+        };
+
+        if let Some(variant) = tag("Variant") {
+            let variant = variant.to_lowercase();
+            if variant.contains("960") || variant.contains("fischer") {
+                state.set_chess960(true);
+            }
+        }
+        Ok(state)
+    }
+
+    // reads the moves of the mainline or a variation (up to its end) starting from the given state
+    fn read_moves(
+        tokens: &mut Peekable<IntoIter<Token>>,
+        mut state: State,
+        is_variation: bool,
+    ) -> Result<(Vec<PgnMove>, Option<Option<Outcome>>), PgnError> {
+        let mut moves: Vec<PgnMove> = Vec::new();
+        let mut previous_state = state.clone();
+        let mut comment_before: Option<String> = None;
+
+        loop {
+            // a game without a result token ends where the tags of the next game start
+            if let Some(Token::Tag(_, _)) = tokens.peek() {
+                return match is_variation {
+                    true => Err(PgnError::UnbalancedVariation),
+                    false => Ok((moves, None)),
+                };
+            }
+
+            match tokens.next() {
+                None if is_variation => return Err(PgnError::UnbalancedVariation),
+                None => return Ok((moves, None)),
+                Some(Token::Result(result)) if !is_variation => return Ok((moves, Some(result))),
+                Some(Token::Result(_)) => return Err(PgnError::UnbalancedVariation),
+                Some(Token::VariationEnd) if is_variation => return Ok((moves, None)),
+                Some(Token::VariationEnd) => return Err(PgnError::UnbalancedVariation),
+                Some(Token::Comment(comment)) => match moves.last_mut() {
+                    Some(last_move) => {
+                        PgnGame::append_comment(&mut last_move.comment_after, comment)
+                    }
+                    None => PgnGame::append_comment(&mut comment_before, comment),
+                },
+                Some(Token::Nag(nag)) => match moves.last_mut() {
+                    Some(last_move) => last_move.nags.push(nag),
+                    None => return Err(PgnError::UnexpectedToken(format!("${}", nag))),
+                },
+                // a variation replaces the move it follows
+                Some(Token::VariationStart) => {
+                    let variation = match PgnGame::read_moves(tokens, previous_state.clone(), true)
+                    {
+                        Ok((variation, _)) => variation,
+                        Err(e) => return Err(e),
+                    };
+                    match moves.last_mut() {
+                        Some(last_move) => last_move.variations.push(variation),
+                        None => return Err(PgnError::UnexpectedToken(String::from("("))),
+                    }
+                }
+                Some(Token::San(san)) => {
+                    let chess_move = match state.parse_san(&san) {
+                        Ok(chess_move) => chess_move,
+                        Err(_) => {
+                            return Err(PgnError::IllegalMove(PgnGame::move_label(&state, &san)))
+                        }
+                    };
+                    previous_state = state.clone();
+                    state.make_move(&chess_move);
+                    moves.push(PgnMove {
+                        chess_move,
+                        nags: Vec::new(),
+                        comment_before: comment_before.take(),
+                        comment_after: None,
+                        variations: Vec::new(),
+                    });
+                }
+                Some(Token::Tag(_, _)) => unreachable!(),
+            }
+        }
+    }
+
+    fn append_comment(comment: &mut Option<String>, text: String) {
+        *comment = match comment.take() {
+            Some(previous) => Some(format!("{} {}", previous, text)),
+            None => Some(text),
+        };
+    }
+
+    // e.g. "12. Nf3" or "12... Nf6"
+    fn move_label(state: &State, san: &str) -> String {
+        match state.turn() {
+            Color::Black => format!("{}... {}", state.fullmove_clock(), san),
+            _ => format!("{}. {}", state.fullmove_clock(), san),
+        }
+    }
+
+    fn outcome(result: &str) -> Option<Outcome> {
+        match result {
+            "1-0" => Some(Outcome::WhiteWon),
+            "0-1" => Some(Outcome::BlackWon),
+            "1/2-1/2" => Some(Outcome::Draw),
+            _ => None,
+        }
+    }
+
+    fn tokenize(pgn: &str) -> Result<Vec<Token>, PgnError> {
+        let mut tokens = Vec::new();
+        let mut chars = pgn.chars().peekable();
+        let mut line_start = true;
+
+        while let Some(char) = chars.next() {
+            match char {
+                '\n' => {
+                    line_start = true;
+                    continue;
+                }
+                // lines starting with '%' are escaped (e.g. for data of other programs)
+                '%' if line_start => {
+                    for char in chars.by_ref() {
+                        if char == '\n' {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                _ if char.is_whitespace() => {}
+                ';' => {
+                    let comment: String = chars.by_ref().take_while(|c| *c != '\n').collect();
+                    tokens.push(Token::Comment(String::from(comment.trim())));
+                    line_start = true;
+                    continue;
+                }
+                '{' => {
+                    let mut comment = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(char) => comment.push(char),
+                            None => return Err(PgnError::UnterminatedComment),
+                        }
+                    }
+                    tokens.push(Token::Comment(
+                        comment.split_whitespace().collect::<Vec<&str>>().join(" "),
+                    ));
+                }
+                '[' => {
+                    let mut tag = String::new();
+                    let mut in_string = false;
+                    loop {
+                        match chars.next() {
+                            Some(']') if !in_string => break,
+                            Some('"') => {
+                                in_string = !in_string;
+                                tag.push('"');
+                            }
+                            Some('\\') if in_string => {
+                                tag.push('\\');
+                                if let Some(char) = chars.next() {
+                                    tag.push(char);
+                                }
+                            }
+                            Some(char) => tag.push(char),
+                            None => return Err(PgnError::InvalidTag(tag)),
+                        }
+                    }
+                    match PgnGame::parse_tag(&tag) {
+                        Some((name, value)) => tokens.push(Token::Tag(name, value)),
+                        None => return Err(PgnError::InvalidTag(tag)),
+                    }
+                }
+                '(' => tokens.push(Token::VariationStart),
+                ')' => tokens.push(Token::VariationEnd),
+                '*' => tokens.push(Token::Result(None)),
+                '$' => {
+                    let mut nag = String::new();
+                    while let Some(char) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                        nag.push(*char);
+                        chars.next();
+                    }
+                    match nag.parse::<u8>() {
+                        Ok(nag) => tokens.push(Token::Nag(nag)),
+                        Err(_) => return Err(PgnError::UnexpectedToken(format!("${}", nag))),
+                    }
+                }
+                _ => {
+                    let mut symbol = String::from(char);
+                    while let Some(char) = chars
+                        .peek()
+                        .filter(|c| !c.is_whitespace() && !"{}()[];$".contains(**c))
+                    {
+                        symbol.push(*char);
+                        chars.next();
+                    }
+                    match PgnGame::symbol_tokens(&symbol) {
+                        Some(mut symbol_tokens) => tokens.append(&mut symbol_tokens),
+                        None => return Err(PgnError::UnexpectedToken(symbol)),
+                    }
+                }
+            }
+            line_start = false;
+        }
+        Ok(tokens)
+    }
+
+    // a tag like 'Event "F/S Return Match"' (without its brackets)
+    fn parse_tag(tag: &str) -> Option<(String, String)> {
+        let tag = tag.trim();
+        let name_length = tag.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?;
+        let (name, value) = tag.split_at(name_length);
+        let value = value.trim();
+        if name.is_empty() || value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+            return None;
+        }
+
+        let mut unescaped = String::new();
+        let mut chars = value[1..value.len() - 1].chars();
+        while let Some(char) = chars.next() {
+            match char {
+                '\\' => unescaped.push(chars.next()?),
+                '"' => return None,
+                char => unescaped.push(char),
+            }
+        }
+        Some((String::from(name), unescaped))
+    }
+
+    // results, move numbers (e.g. "12." or "12...") and moves with their annotations (e.g. "Nf3!?")
+    fn symbol_tokens(symbol: &str) -> Option<Vec<Token>> {
+        match symbol {
+            "1-0" | "0-1" | "1/2-1/2" => {
+                return Some(vec![Token::Result(PgnGame::outcome(symbol))])
+            }
+            _ => {}
+        }
+
+        let mut tokens = Vec::new();
+        let mut san = symbol;
+        if san.starts_with(|c: char| c.is_ascii_digit()) && !san.starts_with("0-0") {
+            let number_length = san.find(|c: char| !c.is_ascii_digit())?;
+            let rest = &san[number_length..];
+            if !rest.starts_with('.') {
+                return None;
+            }
+            san = rest.trim_start_matches('.');
+            if san.is_empty() {
+                return Some(tokens);
+            }
+        }
+
+        let annotation_start = san.find(['!', '?']).unwrap_or(san.len());
+        let (san, annotation) = san.split_at(annotation_start);
+        tokens.push(Token::San(String::from(san)));
+        match annotation {
+            "" => {}
+            "!" => tokens.push(Token::Nag(1)),
+            "?" => tokens.push(Token::Nag(2)),
+            "!!" => tokens.push(Token::Nag(3)),
+            "??" => tokens.push(Token::Nag(4)),
+            "!?" => tokens.push(Token::Nag(5)),
+            "?!" => tokens.push(Token::Nag(6)),
+            _ => return None,
+        }
+        Some(tokens)
+    }
+}
+
+impl PgnMove {
+    pub fn chess_move(&self) -> &Move {
+        &self.chess_move
+    }
+
+    fn annotation(&self) -> Annotation {
+        Annotation {
+            nags: self.nags.clone(),
+            comment_before: self.comment_before.clone(),
+            comment_after: self.comment_after.clone(),
+        }
+    }
+
+    pub fn nags(&self) -> &Vec<u8> {
+        &self.nags
+    }
+
+    pub fn comment_before(&self) -> &Option<String> {
+        &self.comment_before
+    }
+
+    pub fn comment_after(&self) -> &Option<String> {
+        &self.comment_after
+    }
+
+    // alternatives to this move, each one continuing from the position before it
+    pub fn variations(&self) -> &Vec<Vec<PgnMove>> {
+        &self.variations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::library::game::{handle_state, Field};
+
+    const GAME: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+[Annotator "Someone \"quoted\""]
+
+% an escaped line
+1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.} 3... a6!?
+(3... Nf6 4. O-O (4. d3 $1 d6) Nxe4 ; the open defence
+) 4. Ba4 Nf6 5. O-O Be7 1/2-1/2
+"#;
+
+    #[test]
+    fn from_pgn_tags_and_moves() {
+        let games = PgnGame::from_pgn(GAME).unwrap();
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+
+        assert_eq!(game.tags().len(), 8);
+        assert_eq!(game.tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(game.tag("Annotator"), Some("Someone \"quoted\""));
+        assert_eq!(game.tag("ECO"), None);
+        assert_eq!(game.result(), &Some(Outcome::Draw));
+
+        assert_eq!(game.moves().len(), 10);
+        let bb5 = &game.moves()[4];
+        assert_eq!(bb5.chess_move().target_field(), &Field(4, 1));
+        assert_eq!(
+            bb5.comment_after(),
+            &Some(String::from("This opening is called the Ruy Lopez."))
+        );
+        assert_eq!(game.moves()[5].nags(), &vec![5]);
+    }
+
+    #[test]
+    fn from_pgn_variations() {
+        let game = &PgnGame::from_pgn(GAME).unwrap()[0];
+
+        // 3... Nf6 4. O-O (4. d3 $1 d6) Nxe4 instead of 3... a6
+        let variations = game.moves()[5].variations();
+        assert_eq!(variations.len(), 1);
+        let variation = &variations[0];
+        assert_eq!(variation.len(), 3);
+        assert_eq!(variation[0].chess_move().target_field(), &Field(5, 5));
+        assert_eq!(
+            variation[2].comment_after(),
+            &Some(String::from("the open defence"))
+        );
+
+        let nested = &variation[1].variations()[0];
+        assert_eq!(nested.len(), 2);
+        assert_eq!(nested[0].nags(), &vec![1]);
+    }
+
+    #[test]
+    fn from_pgn_states() {
+        let game = &PgnGame::from_pgn(GAME).unwrap()[0];
+        let states = game.states();
+        assert_eq!(states.len(), 11);
+        assert_eq!(
+            states[10].to_fen(),
+            "r1bqk2r/1pppbppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 w kq - 4 6"
+        );
+
+        let game_state = game.game_state();
+        assert_eq!(game_state.tree().depth(), 10);
+        assert_eq!(game_state.current_state(), &states[10]);
+    }
+
+    #[test]
+    fn from_pgn_setup_and_several_games() {
+        let pgn =
+            "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]\n\n1... Kd7 2. e4 *\n\n\
+                   [Event \"?\"]\n1. d4 d5 0-1\n\n1. c4";
+        let games = PgnGame::from_pgn(pgn).unwrap();
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].result(), &None);
+        assert_eq!(
+            games[0].states()[2].to_fen(),
+            "8/3k4/8/8/4P3/8/8/4K3 b - e3 0 2"
+        );
+        assert_eq!(games[1].result(), &Some(Outcome::BlackWon));
+        assert_eq!(games[2].moves().len(), 1);
+    }
+
+    #[test]
+    fn to_pgn() {
+        let game = &PgnGame::from_pgn(GAME).unwrap()[0];
+        assert_eq!(
+            game.to_pgn(),
+            r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+[Annotator "Someone \"quoted\""]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.} 3... a6 $5
+(3... Nf6 4. O-O (4. d3 $1 d6) 4... Nxe4 {the open defence}) 4. Ba4 Nf6 5. O-O
+Be7 1/2-1/2
+
+"#
+        );
+
+        // writing and reading a game again doesn't change it
+        assert_eq!(&PgnGame::from_pgn(&game.to_pgn()).unwrap()[0], game);
+    }
+
+    #[test]
+    fn from_game_state() {
+        let mut game = GameState::new(State::new(None));
+        for san in ["f3", "e5", "g4", "Qh4"] {
+            let chess_move = game.current_state().parse_san(san).unwrap();
+            let mut state = game.current_state().clone();
+            state.make_move(&chess_move);
+            game.push_state(state);
+        }
+        assert_eq!(
+            PgnGame::from_game_state(&game).to_pgn(),
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"?\"]\n\
+             [Black \"?\"]\n[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n\n"
+        );
+
+        // a custom start position is written as FEN
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 1";
+        let game = GameState::new(State::new(Some(String::from(fen))));
+        let pgn = PgnGame::from_game_state(&game);
+        assert_eq!(pgn.tag("SetUp"), Some("1"));
+        assert_eq!(pgn.tag("FEN"), Some(fen));
+        assert!(pgn.to_pgn().ends_with("]\n\n*\n\n"));
+    }
+
+    #[test]
+    fn game_state_keeps_result_tags_and_annotations() {
+        let game = &PgnGame::from_pgn(GAME).unwrap()[0];
+        let mut game_state = game.game_state();
+        assert_eq!(game_state.tags(), game.tags());
+
+        // the draw was agreed, so the game can't be continued
+        assert_eq!(
+            game_state.result(),
+            &Some(GameResult::draw(Termination::Agreement))
+        );
+        let depth = game_state.tree().depth();
+        let state = game_state.current_state().clone();
+        let new_state = state.execute_move(&state.parse_san("d3").unwrap());
+        handle_state(Ok(new_state), &mut game_state);
+        assert_eq!(game_state.tree().depth(), depth);
+        assert!(game_state.undo());
+        assert_eq!(game_state.result(), &None);
+        assert!(game_state.redo());
+        assert!(game_state.is_over());
+
+        let mut node = game_state.tree().root();
+        for _ in 0..5 {
+            node = &node.children()[0];
+        }
+        assert_eq!(
+            node.annotation().comment_after,
+            Some(String::from("This opening is called the Ruy Lopez."))
+        );
+        assert_eq!(node.children()[0].annotation().nags, vec![5]);
+
+        // a win on time and a game in progress
+        let game = &PgnGame::from_pgn("[Termination \"time forfeit\"]\n1. e4 e5 0-1").unwrap()[0];
+        assert_eq!(
+            game.game_state().result(),
+            &Some(GameResult::new(Outcome::BlackWon, Termination::TimeForfeit))
+        );
+        let game = &PgnGame::from_pgn("1. e4 e5 *").unwrap()[0];
+        assert!(!game.game_state().is_over());
+
+        // a checkmate keeps its termination
+        let game = &PgnGame::from_pgn("1. f3 e5 2. g4 Qh4# 0-1").unwrap()[0];
+        assert_eq!(
+            game.game_state().result(),
+            &Some(GameResult::win(&Color::Black, Termination::Checkmate))
+        );
+    }
+
+    #[test]
+    fn from_game_state_keeps_tags_annotations_and_result() {
+        let pgn = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1-0"]
+[Annotator "Someone \"quoted\""]
+
+{A classical game.} 1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy
+Lopez.} 3... a6 $5 (3... Nf6 4. O-O (4. d3 $1 d6) 4... Nxe4 {the open defence})
+4. Ba4 Nf6 5. O-O Be7 $6 1-0
+
+"#;
+        let mut game_state = PgnGame::from_pgn(pgn).unwrap()[0].game_state();
+        assert_eq!(PgnGame::from_game_state(&game_state).to_pgn(), pgn);
+
+        // the result stays even if the game isn't at its end
+        game_state.undo();
+        assert_eq!(PgnGame::from_game_state(&game_state).to_pgn(), pgn);
+
+        // missing tags of the roster are filled in, others are kept
+        let game = &PgnGame::from_pgn("[White \"Me\"]\n[ECO \"C60\"]\n\n1. e4 *").unwrap()[0];
+        let pgn = PgnGame::from_game_state(&game.game_state());
+        assert_eq!(pgn.tags().len(), 8);
+        assert_eq!(pgn.tag("White"), Some("Me"));
+        assert_eq!(pgn.tag("Black"), Some("?"));
+        assert_eq!(pgn.tags()[7], (String::from("ECO"), String::from("C60")));
+
+        // a resignation is written as the result as well
+        let mut game_state = GameState::new(State::new(None));
+        let state = game_state.current_state().clone();
+        game_state.push_state(state.execute_move(&state.parse_san("e4").unwrap()));
+        game_state.resign(&Color::Black);
+        game_state.undo();
+        let pgn = PgnGame::from_game_state(&game_state);
+        assert_eq!(pgn.result(), &Some(Outcome::WhiteWon));
+        assert!(pgn.to_pgn().ends_with("\n\n1. e4 1-0\n\n"));
+    }
+
+    #[test]
+    fn game_state_variations() {
+        let game = &PgnGame::from_pgn("1. e4 e5 (1... c5 2. Nf3 (2. c3) d6) 2. Nf3 *").unwrap()[0];
+        let game_state = game.game_state();
+        assert!(game_state.tree().is_mainline());
+        assert_eq!(game_state.tree().depth(), 3);
+        assert_eq!(game_state.tree().root().children()[0].children().len(), 2);
+        assert!(PgnGame::from_game_state(&game_state)
+            .to_pgn()
+            .ends_with("\n\n1. e4 e5 (1... c5 2. Nf3 (2. c3) 2... d6) 2. Nf3 *\n\n"));
+    }
+
+    #[test]
+    fn to_pgn_line_length() {
+        let movetext = "1. Nf3 Nf6 2. Ng1 Ng8 ".repeat(20);
+        let game = &PgnGame::from_pgn(&movetext).unwrap()[0];
+        let pgn = game.to_pgn();
+        assert!(pgn.lines().all(|line| line.len() <= 80));
+        assert!(pgn.lines().any(|line| line.len() > 70));
+        assert_eq!(&PgnGame::from_pgn(&pgn).unwrap()[0], game);
+    }
+
+    #[test]
+    fn from_pgn_errors() {
+        assert_eq!(PgnGame::from_pgn(" \n"), Err(PgnError::NoGame));
+        assert_eq!(
+            PgnGame::from_pgn("[Event \"?]\n1. e4"),
+            Err(PgnError::InvalidTag(String::from("Event \"?]\n1. e4")))
+        );
+        assert_eq!(
+            PgnGame::from_pgn("1. e4 {comment"),
+            Err(PgnError::UnterminatedComment)
+        );
+        assert_eq!(
+            PgnGame::from_pgn("1. e4 (1. d4 e5"),
+            Err(PgnError::UnbalancedVariation)
+        );
+        assert_eq!(
+            PgnGame::from_pgn("1. e4 e5)"),
+            Err(PgnError::UnbalancedVariation)
+        );
+        assert_eq!(
+            PgnGame::from_pgn("1. e4 e5 2. Ke3"),
+            Err(PgnError::IllegalMove(String::from("2. Ke3")))
+        );
+        assert_eq!(
+            PgnGame::from_pgn("1. e4 Nf6 2. e5 Nd5 3. Qh5 d6?? 4. Qxf7 Kd7 5. Qd7"),
+            Err(PgnError::IllegalMove(String::from("5. Qd7")))
+        );
+        assert_eq!(
+            PgnGame::from_pgn("[FEN \"8/8/8\"]\n1. e4"),
+            Err(PgnError::InvalidFen(FenError::WrongNumberOfFields(1)))
+        );
+        assert_eq!(
+            PgnGame::from_pgn("1. e4 e5 2. Nf3?!! Nc6"),
+            Err(PgnError::UnexpectedToken(String::from("Nf3?!!")))
+        );
+    }
+}
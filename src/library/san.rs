@@ -0,0 +1,338 @@
+use crate::library::bitboard;
+use crate::library::game::{Field, Move, MoveError, MoveFlag, PieceType, State};
+
+/*
+    Standard Algebraic Notation (SAN) names a move by the moving piece and its target field, e.g. "e4", "Nf3",
+    "exd5", "Nbd7", "R1e2", "e8=Q", "O-O" or "O-O-O". The start field is only given (partially) when more than one
+    piece of the same type could move to the target field.
+
+    Parsing is lenient about the details that follow from the position anyway: the capture sign "x" is optional,
+    check and mate suffixes ("+", "#") as well as annotations ("!", "?") are ignored and a promotion may be written
+    without the equals sign ("e8Q" or "e8q"). Castling is accepted with zeros ("0-0") or as the king move ("Kg1").
+*/
+impl State {
+    pub fn parse_san(&self, san: &str) -> Result<Move, MoveError> {
+        let san = san.trim().trim_end_matches(|c| "+#!?".contains(c));
+
+        match san {
+            "O-O" | "0-0" => return self.parse_castling(true),
+            "O-O-O" | "0-0-0" => return self.parse_castling(false),
+            _ => {}
+        }
+
+        let mut chars: Vec<char> = san.chars().collect();
+
+        // the piece a pawn is promoted to comes last (following the target field)
+        let promotion = match chars
+            .last()
+            .and_then(|c| State::san_piecetype(c.to_ascii_uppercase()))
+        {
+            Some(piecetype) if chars.len() >= 3 && chars[chars.len() - 2].is_ascii_digit() => {
+                chars.pop();
+                Some(piecetype)
+            }
+            Some(piecetype) if chars.len() >= 4 && chars[chars.len() - 2] == '=' => {
+                chars.truncate(chars.len() - 2);
+                Some(piecetype)
+            }
+            _ => None,
+        };
+
+        // the target field is always given in full
+        if chars.len() < 2 {
+            return Err(MoveError::InvalidNotation);
+        }
+        let target_field = match State::san_field(chars[chars.len() - 2], chars[chars.len() - 1]) {
+            Some(field) => field,
+            None => return Err(MoveError::InvalidNotation),
+        };
+        chars.truncate(chars.len() - 2);
+
+        // pieces are given by their upper case letter, pawns by nothing (or the file they capture from)
+        let piecetype = match chars.first().and_then(|c| State::san_piecetype(*c)) {
+            Some(piecetype) => {
+                chars.remove(0);
+                piecetype
+            }
+            None => PieceType::Pawn,
+        };
+
+        // whatever is left narrows down the start field (e.g. "b" in "Nbd7" or "e" in "exd5")
+        if chars.last() == Some(&'x') || chars.last() == Some(&':') {
+            chars.pop();
+        }
+        let (mut start_file, mut start_rank) = (None, None);
+        for char in chars {
+            match char {
+                'a'..='h' if start_file.is_none() && start_rank.is_none() => {
+                    start_file = Some(char as usize - 'a' as usize)
+                }
+                '1'..='8' if start_rank.is_none() => {
+                    start_rank = Some(char as usize - '1' as usize)
+                }
+                _ => return Err(MoveError::InvalidNotation),
+            }
+        }
+
+        let matches_start_field = |field: &Field| {
+            start_file.unwrap_or(field.1) == field.1 && start_rank.unwrap_or(field.0) == field.0
+        };
+
+        let candidates: Vec<Move> = self
+            .legal_moves()
+            .into_iter()
+            .filter(|chess_move| {
+                chess_move.piece().piecetype() == &piecetype
+                    && chess_move.target_field() == &target_field
+                    && matches_start_field(chess_move.start_field())
+            })
+            .collect();
+
+        // the promotions of a single pawn are the only moves sharing start and target field
+        let promoting = candidates
+            .iter()
+            .any(|chess_move| chess_move.is_promoting());
+        let candidates: Vec<Move> = match (promoting, promotion) {
+            (true, None) => {
+                return match candidates
+                    .iter()
+                    .all(|m| m.start_field() == candidates[0].start_field())
+                {
+                    true => Err(MoveError::PromotionRequired),
+                    false => Err(MoveError::AmbiguousMove),
+                }
+            }
+            (false, Some(_)) if !candidates.is_empty() => return Err(MoveError::InvalidPromotion),
+            _ => candidates
+                .into_iter()
+                .filter(|chess_move| chess_move.promotion() == &promotion)
+                .collect(),
+        };
+
+        match candidates.len() {
+            1 => Ok(candidates[0].clone()),
+            0 => {
+                Err(self.san_move_error(&piecetype, &target_field, promotion, matches_start_field))
+            }
+            _ => Err(MoveError::AmbiguousMove),
+        }
+    }
+
+    fn parse_castling(&self, kingside: bool) -> Result<Move, MoveError> {
+        let color = *self.turn();
+        let king_field = match self.board().king_field(&color) {
+            Some(field) => field,
+            None => return Err(MoveError::NoPieceSelected),
+        };
+
+        let castling = self
+            .legal_moves_from(&king_field)
+            .into_iter()
+            .find(|chess_move| match chess_move.flag() {
+                MoveFlag::KingsideCastle => kingside,
+                MoveFlag::QueensideCastle => !kingside,
+                _ => false,
+            });
+        if let Some(chess_move) = castling {
+            return Ok(chess_move);
+        }
+
+        // in standard chess the king moves two fields, in Chess960 it moves onto the rook
+        let target_file = match self.is_chess960() {
+            true => self.castle_availability().rook_file(&color, kingside),
+            false if king_field.1 == 4 && kingside => Some(6),
+            false if king_field.1 == 4 => Some(2),
+            false => None,
+        };
+        match target_file {
+            Some(file) => {
+                let chess_move = Move::new(&king_field, &Field(king_field.0, file), self);
+                match self.is_move_legal(&chess_move) {
+                    Ok(_) => Err(MoveError::CastlingNotAvailable),
+                    Err(e) => Err(e),
+                }
+            }
+            None => Err(MoveError::CastlingNotAvailable),
+        }
+    }
+
+    // explains why none of the player's pieces of the given type can make the move
+    fn san_move_error(
+        &self,
+        piecetype: &PieceType,
+        target_field: &Field,
+        promotion: Option<PieceType>,
+        matches_start_field: impl Fn(&Field) -> bool,
+    ) -> MoveError {
+        let mut error = MoveError::NoPieceSelected;
+        for start_field in bitboard::fields(self.board().pieces(self.turn(), piecetype)) {
+            if !matches_start_field(&start_field) {
+                continue;
+            }
+            let mut chess_move = Move::new(&start_field, target_field, self);
+            chess_move.promotion = promotion;
+
+            // prefer the reason of a piece that could at least reach the target field
+            match self.is_move_legal(&chess_move) {
+                Err(MoveError::PieceCantReachTarget) if error != MoveError::NoPieceSelected => {}
+                Err(e) => error = e,
+                Ok(_) => {}
+            }
+            if error != MoveError::PieceCantReachTarget && error != MoveError::NoPieceSelected {
+                break;
+            }
+        }
+        error
+    }
+
+    fn san_piecetype(char: char) -> Option<PieceType> {
+        match char {
+            'K' => Some(PieceType::King),
+            'Q' => Some(PieceType::Queen),
+            'R' => Some(PieceType::Rook),
+            'B' => Some(PieceType::Bishop),
+            'N' => Some(PieceType::Knight),
+            _ => None,
+        }
+    }
+
+    fn san_field(file: char, rank: char) -> Option<Field> {
+        match (file, rank) {
+            ('a'..='h', '1'..='8') => Some(Field(
+                rank as usize - '1' as usize,
+                file as usize - 'a' as usize,
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::library::game::Color;
+
+    fn san_move(fen: &str, san: &str) -> Result<(Field, Field, Option<PieceType>), MoveError> {
+        let state = State::load_game_from_fen(String::from(fen));
+        match state.parse_san(san) {
+            Ok(chess_move) => Ok((
+                chess_move.start_field().clone(),
+                chess_move.target_field().clone(),
+                *chess_move.promotion(),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    #[test]
+    fn parse_san_pawn_and_piece_moves() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san_move(fen, "e4"), Ok((Field(1, 4), Field(3, 4), None)));
+        assert_eq!(san_move(fen, "e3"), Ok((Field(1, 4), Field(2, 4), None)));
+        assert_eq!(san_move(fen, "Nf3"), Ok((Field(0, 6), Field(2, 5), None)));
+        assert_eq!(san_move(fen, "Ngf3+"), Ok((Field(0, 6), Field(2, 5), None)));
+        assert_eq!(san_move(fen, "e5"), Err(MoveError::PieceCantReachTarget));
+        assert_eq!(san_move(fen, "Bc4"), Err(MoveError::NoPathToTarget));
+        assert_eq!(san_move(fen, "Nf6"), Err(MoveError::PieceCantReachTarget));
+        assert_eq!(san_move(fen, "e9"), Err(MoveError::InvalidNotation));
+        assert_eq!(san_move(fen, "Xe4"), Err(MoveError::InvalidNotation));
+        assert_eq!(san_move(fen, "hello"), Err(MoveError::InvalidNotation));
+    }
+
+    #[test]
+    fn parse_san_captures_and_disambiguation() {
+        // knights on b8 and f6 can both reach d7, rooks on a1 and a5 can both reach a3
+        let fen = "rn1qkb1r/ppp2ppp/5n2/R2pp3/4P3/8/PPPP1PPP/RNBQKBN1 b Qkq - 0 1";
+        assert_eq!(san_move(fen, "Nbd7"), Ok((Field(7, 1), Field(6, 3), None)));
+        assert_eq!(san_move(fen, "Nfd7"), Ok((Field(5, 5), Field(6, 3), None)));
+        assert_eq!(san_move(fen, "Nd7"), Err(MoveError::AmbiguousMove));
+        assert_eq!(san_move(fen, "dxe4"), Ok((Field(4, 3), Field(3, 4), None)));
+        assert_eq!(san_move(fen, "Nxe4"), Ok((Field(5, 5), Field(3, 4), None)));
+
+        let fen = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san_move(fen, "R1a3"), Ok((Field(0, 0), Field(2, 0), None)));
+        assert_eq!(san_move(fen, "R5a3"), Ok((Field(4, 0), Field(2, 0), None)));
+        assert_eq!(san_move(fen, "Ra3"), Err(MoveError::AmbiguousMove));
+        assert_eq!(san_move(fen, "Rb1"), Ok((Field(0, 0), Field(0, 1), None)));
+    }
+
+    #[test]
+    fn parse_san_en_passant_and_pin() {
+        let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(san_move(fen, "exd6"), Ok((Field(4, 4), Field(5, 3), None)));
+
+        // the knight on e2 is pinned by the rook on e8
+        let fen = "4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1";
+        assert_eq!(san_move(fen, "Nc3"), Err(MoveError::PieceIsPinned));
+    }
+
+    #[test]
+    fn parse_san_promotion() {
+        let fen = "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(
+            san_move(fen, "a8=Q"),
+            Ok((Field(6, 0), Field(7, 0), Some(PieceType::Queen)))
+        );
+        assert_eq!(
+            san_move(fen, "a8=Q+"),
+            Ok((Field(6, 0), Field(7, 0), Some(PieceType::Queen)))
+        );
+        assert_eq!(
+            san_move(fen, "a8N"),
+            Ok((Field(6, 0), Field(7, 0), Some(PieceType::Knight)))
+        );
+        assert_eq!(
+            san_move(fen, "axb8=r"),
+            Ok((Field(6, 0), Field(7, 1), Some(PieceType::Rook)))
+        );
+        assert_eq!(san_move(fen, "a8"), Err(MoveError::PromotionRequired));
+        assert_eq!(san_move(fen, "a8=K"), Err(MoveError::InvalidPromotion));
+        assert_eq!(san_move(fen, "Kd2=Q"), Err(MoveError::InvalidPromotion));
+    }
+
+    #[test]
+    fn parse_san_castling() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san_move(fen, "O-O"), Ok((Field(0, 4), Field(0, 6), None)));
+        assert_eq!(san_move(fen, "0-0-0"), Ok((Field(0, 4), Field(0, 2), None)));
+        assert_eq!(san_move(fen, "Kg1"), Ok((Field(0, 4), Field(0, 6), None)));
+
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w Qkq - 0 1";
+        assert_eq!(san_move(fen, "O-O"), Err(MoveError::CastlingNotAvailable));
+
+        // the rook on d8 attacks d1
+        let fen = "3rk3/8/8/8/8/8/8/R3K2R w KQ - 0 1";
+        assert_eq!(san_move(fen, "O-O-O"), Err(MoveError::CastlingThroughCheck));
+
+        // in Chess960 the king moves onto the rook
+        let fen = "r4k1r/8/8/8/8/8/8/RK5R w AHah - 0 1";
+        assert_eq!(san_move(fen, "O-O-O"), Ok((Field(0, 1), Field(0, 0), None)));
+        assert_eq!(san_move(fen, "O-O"), Ok((Field(0, 1), Field(0, 7), None)));
+
+        // the side follows from the rook, both when reading and writing the move
+        let state = State::load_game_from_fen(String::from(fen));
+        let castling = state.parse_san("O-O-O").unwrap();
+        assert_eq!(castling.flag(), &MoveFlag::QueensideCastle);
+        assert_eq!(castling.to_san(&state), "O-O-O");
+        let castling = state.parse_san("O-O").unwrap();
+        assert_eq!(castling.flag(), &MoveFlag::KingsideCastle);
+        // the rook on f1 gives check
+        assert_eq!(castling.to_san(&state), "O-O+");
+    }
+
+    #[test]
+    fn parse_san_checkmate() {
+        let state = State::new(None);
+        let state = ["f3", "e5", "g4"].iter().fold(state, |state, san| {
+            let chess_move = state.parse_san(san).unwrap();
+            let mut state = state;
+            state.make_move(&chess_move);
+            state
+        });
+        let chess_move = state.parse_san("Qh4#").unwrap();
+        assert_eq!(chess_move.start_field(), &Field(7, 3));
+        assert_eq!(chess_move.target_field(), &Field(3, 7));
+        assert_eq!(state.turn(), &Color::Black);
+    }
+}
@@ -0,0 +1,178 @@
+use crate::library::game::{FenError, State};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/*
+    With the 'serde' feature, positions, moves and games implement serde's Serialize and Deserialize. In JSON
+    they look like this:
+
+        State               {"fen": "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", "chess960": false}
+        Field               [rank, file], counted from 0, e.g. e4 is [3, 4]
+        Color               "White", "Black" or "None"
+        PieceType           "Rook", "Knight", "Bishop", "Queen", "King", "Pawn" or "None"
+        Piece               {"color": "White", "piecetype": "Knight"}
+        CastleAvailability  {"white_king": 7, "white_queen": 0, "black_king": null, "black_queen": null},
+                            i.e. the file of the rook each side may still castle with
+        Move                {"piece": Piece, "start_field": Field, "target_field": Field, "promotion": PieceType
+                            or null, "captured": Piece or null, "flag": "Normal", "DoublePush", "EnPassant",
+                            "KingsideCastle" or "QueensideCastle"}
+        GameResult          {"outcome": "WhiteWon", "BlackWon" or "Draw", "termination": "Checkmate", ...}
+        GameTree            {"root": GameNode, "path": [child indices], "redo_path": [child indices]}
+        GameNode            {"state": State, "children": [GameNode, ...], "annotation": Annotation,
+                            "result": GameResult or null}
+        Annotation          {"nags": [numbers], "comment_before": string or null, "comment_after": string or null}
+        GameState           {"selected_field": [rank, file] or null, "pending_promotion": string or null,
+                            "draw_offered": bool, "tree": GameTree, "result": GameResult or null,
+                            "tags": [[name, value], ...]}
+
+    A state is stored as its FEN string, so reading it back checks the position like 'State::from_fen' does.
+    Chess960 is stored separately because a standard start position is a valid Chess960 position as well.
+    Moves are only written, since a move read on its own can't be checked against a position. Games are checked
+    when they are read: the path and redo path have to lead to existing nodes, each state in the tree has to
+    follow from its parent by a legal move and the result has to match the current position (or be declared in
+    it, e.g. a resignation).
+*/
+#[derive(Serialize, Deserialize)]
+pub struct SerializedState {
+    fen: String,
+    chess960: bool,
+}
+
+impl From<State> for SerializedState {
+    fn from(state: State) -> SerializedState {
+        SerializedState {
+            fen: state.to_fen(),
+            chess960: state.is_chess960(),
+        }
+    }
+}
+
+impl TryFrom<SerializedState> for State {
+    type Error = FenError;
+
+    fn try_from(serialized: SerializedState) -> Result<State, FenError> {
+        match State::from_fen(&serialized.fen) {
+            Ok(mut state) => {
+                state.set_chess960(serialized.chess960);
+                Ok(state)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::library::game::{Color, Field, GameResult, GameState, State, Termination};
+
+    #[test]
+    fn state() {
+        let state = State::new(Some(String::from(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 3 12",
+        )));
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(
+            json,
+            "{\"fen\":\"r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 3 12\",\
+             \"chess960\":false}"
+        );
+        assert_eq!(serde_json::from_str::<State>(&json).unwrap(), state);
+
+        // the Chess960 mode is kept even if the position looks like standard chess
+        let mut state = State::new(None);
+        state.set_chess960(true);
+        let json = serde_json::to_string(&state).unwrap();
+        let read = serde_json::from_str::<State>(&json).unwrap();
+        assert!(read.is_chess960());
+        assert_eq!(read.zobrist_key(), state.zobrist_key());
+
+        // invalid positions are rejected
+        assert!(serde_json::from_str::<State>(
+            "{\"fen\":\"8/8/8/8 w - - 0 1\",\"chess960\":false}"
+        )
+        .is_err());
+        assert!(serde_json::from_str::<State>(
+            "{\"fen\":\"4k3/8/8/8/8/8/8/8 w - - 0 1\",\"chess960\":false}"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn field_and_move() {
+        assert_eq!(serde_json::to_string(&Field(3, 4)).unwrap(), "[3,4]");
+        assert_eq!(serde_json::from_str::<Field>("[3,4]").unwrap(), Field(3, 4));
+
+        let state = State::new(Some(String::from(
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        )));
+        let chess_move = state.parse_san("exf6").unwrap();
+        let json = serde_json::to_string(&chess_move).unwrap();
+        assert_eq!(
+            json,
+            "{\"piece\":{\"color\":\"White\",\"piecetype\":\"Pawn\"},\"start_field\":[4,4],\
+             \"target_field\":[5,5],\"promotion\":null,\
+             \"captured\":{\"color\":\"Black\",\"piecetype\":\"Pawn\"},\"flag\":\"EnPassant\"}"
+        );
+    }
+
+    #[test]
+    fn game_state() {
+        let mut game = GameState::new(State::new(None));
+        for san in ["e4", "e5", "Nf3"] {
+            let state = game.current_state();
+            game.push_state(state.execute_move(&state.parse_san(san).unwrap()));
+        }
+        // a variation 2. Nc3 and back to the mainline
+        game.undo();
+        let state = game.current_state();
+        game.push_state(state.execute_move(&state.parse_san("Nc3").unwrap()));
+        assert!(game.previous_variation());
+
+        let json = serde_json::to_string(&game).unwrap();
+        let read = serde_json::from_str::<GameState>(&json).unwrap();
+        assert_eq!(read.current_state(), game.current_state());
+        assert_eq!(read.tree(), game.tree());
+        assert_eq!(read.result(), game.result());
+        assert_eq!(read.tree().line().len(), 4);
+    }
+
+    #[test]
+    fn invalid_game_state() {
+        let mut game = GameState::new(State::new(None));
+        for san in ["e4", "e5"] {
+            let state = game.current_state();
+            game.push_state(state.execute_move(&state.parse_san(san).unwrap()));
+        }
+        game.undo();
+        let json = serde_json::to_value(&game).unwrap();
+        assert!(serde_json::from_value::<GameState>(json.clone()).is_ok());
+
+        let read = |change: &dyn Fn(&mut serde_json::Value)| {
+            let mut json = json.clone();
+            change(&mut json);
+            serde_json::from_value::<GameState>(json)
+        };
+        // paths to nodes that don't exist
+        assert!(read(&|json| json["tree"]["path"] = serde_json::json!([3])).is_err());
+        assert!(read(&|json| json["tree"]["path"] = serde_json::json!([0, 0, 0])).is_err());
+        assert!(read(&|json| json["tree"]["redo_path"] = serde_json::json!([1])).is_err());
+        // a state that no move leads to
+        assert!(read(&|json| {
+            json["tree"]["root"]["children"][0]["state"]["fen"] =
+                serde_json::json!("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1")
+        })
+        .is_err());
+        // a result that doesn't match the position
+        let resigned = GameResult::win(&Color::White, Termination::Resignation);
+        assert!(read(&|json| json["result"] = serde_json::to_value(resigned).unwrap()).is_err());
+        assert!(read(&|json| json["selected_field"] = serde_json::json!([8, 0])).is_err());
+
+        // a declared result is kept
+        game.redo();
+        game.resign(&Color::Black);
+        let json = serde_json::to_string(&game).unwrap();
+        let read = serde_json::from_str::<GameState>(&json).unwrap();
+        assert_eq!(read.result(), &Some(resigned));
+    }
+}
@@ -0,0 +1,408 @@
+use crate::library::game::{GameResult, State};
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+
+/*
+    The game tree holds every position reached in a game, including the variations explored by taking back moves
+    and playing different ones. Each node stores the state after a move and the states that may follow it, where
+    the first child continues the mainline and the others are variations.
+
+    The current node is given by the path of child indices leading to it from the root (the initial position).
+    Going back remembers the child that was left, so that going forward again returns to the same variation.
+
+    A node may also carry the annotation of the move leading to it (as read from PGN) and a result that doesn't
+    follow from its position, e.g. a resignation.
+*/
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameNode {
+    state: State,
+    children: Vec<GameNode>,
+    annotation: Annotation,
+    result: Option<GameResult>,
+}
+
+// the comments and numeric annotation glyphs (e.g. $1 for a good move) of a move
+#[derive(PartialEq, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Annotation {
+    pub nags: Vec<u8>,
+    pub comment_before: Option<String>,
+    pub comment_after: Option<String>,
+}
+
+impl GameNode {
+    fn new(state: State) -> GameNode {
+        GameNode {
+            state,
+            children: Vec::new(),
+            annotation: Annotation::default(),
+            result: None,
+        }
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn annotation(&self) -> &Annotation {
+        &self.annotation
+    }
+
+    // the result declared in this position (resignations, draw agreements and claims)
+    pub fn result(&self) -> &Option<GameResult> {
+        &self.result
+    }
+
+    // the mainline continuation comes first, followed by the variations
+    pub fn children(&self) -> &Vec<GameNode> {
+        &self.children
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RawGameTree")
+)]
+pub struct GameTree {
+    root: GameNode,
+    path: Vec<usize>,
+    redo_path: Vec<usize>,
+}
+
+impl GameTree {
+    pub fn new(initial_state: State) -> GameTree {
+        GameTree {
+            root: GameNode::new(initial_state),
+            path: Vec::new(),
+            redo_path: Vec::new(),
+        }
+    }
+
+    pub fn root(&self) -> &GameNode {
+        &self.root
+    }
+
+    pub fn current(&self) -> &GameNode {
+        self.node(&self.path)
+    }
+
+    pub fn current_state(&self) -> &State {
+        &self.current().state
+    }
+
+    // the number of moves played to reach the current node
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    // whether the current node is part of the mainline
+    pub fn is_mainline(&self) -> bool {
+        self.path.iter().all(|index| *index == 0)
+    }
+
+    // the states leading from the initial position to the current one (both included)
+    pub fn line(&self) -> Vec<&State> {
+        let mut node = &self.root;
+        let mut line = vec![&node.state];
+        for index in self.path.iter() {
+            node = &node.children[*index];
+            line.push(&node.state);
+        }
+        line
+    }
+
+    // the last node of the mainline
+    pub fn mainline_end(&self) -> &GameNode {
+        let mut node = &self.root;
+        while let Some(child) = node.children.first() {
+            node = child;
+        }
+        node
+    }
+
+    // the states of the mainline from the initial position to its end
+    pub fn mainline(&self) -> Vec<&State> {
+        let mut node = &self.root;
+        let mut mainline = vec![&node.state];
+        while let Some(child) = node.children.first() {
+            node = child;
+            mainline.push(&node.state);
+        }
+        mainline
+    }
+
+    // continues with the given state, which becomes a new variation unless it already follows the current one
+    pub fn push(&mut self, state: State) {
+        let node = self.node_mut(&self.path.clone());
+        let index = match node.children.iter().position(|child| child.state == state) {
+            Some(index) => index,
+            None => {
+                node.children.push(GameNode::new(state));
+                node.children.len() - 1
+            }
+        };
+        self.path.push(index);
+        self.redo_path.clear();
+    }
+
+    pub fn annotate(&mut self, annotation: Annotation) {
+        self.node_mut(&self.path.clone()).annotation = annotation;
+    }
+
+    pub fn set_result(&mut self, result: Option<GameResult>) {
+        self.node_mut(&self.path.clone()).result = result;
+    }
+
+    pub fn back(&mut self) -> bool {
+        match self.path.pop() {
+            Some(index) => {
+                self.redo_path.push(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // returns to the variation that was left by going back, otherwise follows the mainline
+    pub fn forward(&mut self) -> bool {
+        if self.current().children.is_empty() {
+            return false;
+        }
+        let index = self.redo_path.pop().unwrap_or(0);
+        self.path.push(index);
+        true
+    }
+
+    // switches to the previous / next sibling variation of the current node
+    pub fn previous_variation(&mut self) -> bool {
+        self.switch_variation(-1)
+    }
+
+    pub fn next_variation(&mut self) -> bool {
+        self.switch_variation(1)
+    }
+
+    // makes the line leading to the current node the mainline (at every branching point)
+    pub fn promote_to_mainline(&mut self) -> bool {
+        if self.is_mainline() {
+            return false;
+        }
+        let mut node = &mut self.root;
+        for index in self.path.iter_mut() {
+            let child = node.children.remove(*index);
+            node.children.insert(0, child);
+            node = &mut node.children[0];
+            *index = 0;
+        }
+        self.redo_path.clear();
+        true
+    }
+
+    // removes the current node with all moves following it and goes back to the previous one
+    pub fn delete_current(&mut self) -> bool {
+        let index = match self.path.pop() {
+            Some(index) => index,
+            None => return false,
+        };
+        let parent = self.node_mut(&self.path.clone());
+        parent.children.remove(index);
+        self.redo_path.clear();
+        true
+    }
+
+    fn switch_variation(&mut self, offset: isize) -> bool {
+        let index = match self.path.last() {
+            Some(index) => *index as isize + offset,
+            None => return false,
+        };
+        let siblings = self.node(&self.path[..self.path.len() - 1]).children.len();
+        if index < 0 || index as usize >= siblings {
+            return false;
+        }
+        *self.path.last_mut().unwrap() = index as usize;
+        self.redo_path.clear();
+        true
+    }
+
+    fn node(&self, path: &[usize]) -> &GameNode {
+        path.iter()
+            .fold(&self.root, |node, index| &node.children[*index])
+    }
+
+    fn node_mut(&mut self, path: &[usize]) -> &mut GameNode {
+        path.iter()
+            .fold(&mut self.root, |node, index| &mut node.children[*index])
+    }
+}
+
+// a tree as it is read, before its paths and moves are checked
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawGameTree {
+    root: GameNode,
+    path: Vec<usize>,
+    redo_path: Vec<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawGameTree> for GameTree {
+    type Error = String;
+
+    fn try_from(raw: RawGameTree) -> Result<GameTree, String> {
+        // going forward follows the redo path from its end, so it has to continue the path
+        let mut node = &raw.root;
+        for index in raw.path.iter().chain(raw.redo_path.iter().rev()) {
+            node = match node.children.get(*index) {
+                Some(child) => child,
+                None => {
+                    return Err(format!(
+                        "Invalid game, the path leads to no node ({})",
+                        index
+                    ))
+                }
+            };
+        }
+        GameTree::check_moves(&raw.root)?;
+
+        Ok(GameTree {
+            root: raw.root,
+            path: raw.path,
+            redo_path: raw.redo_path,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl GameTree {
+    // every state has to be reached from the one before by a legal move
+    fn check_moves(node: &GameNode) -> Result<(), String> {
+        for child in node.children.iter() {
+            let is_legal = node.state.legal_moves().iter().any(|chess_move| {
+                let mut state = node.state.clone();
+                state.make_move(chess_move);
+                state == child.state
+            });
+            if !is_legal {
+                return Err(format!(
+                    "Invalid game, no move leads from {} to {}",
+                    node.state.to_fen(),
+                    child.state.to_fen()
+                ));
+            }
+            GameTree::check_moves(child)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn play(tree: &mut GameTree, input: &str) {
+        let state =
+            State::perform_turn_from_input(String::from(input), tree.current_state()).unwrap();
+        tree.push(state);
+    }
+
+    fn current_fen(tree: &GameTree) -> String {
+        tree.current_state().to_fen()
+    }
+
+    #[test]
+    fn variations_are_kept() {
+        let mut tree = GameTree::new(State::new(None));
+        play(&mut tree, "e4");
+        play(&mut tree, "e5");
+        let after_e5 = current_fen(&tree);
+
+        // taking back a move and playing a different one adds a variation
+        assert!(tree.back());
+        play(&mut tree, "c5");
+        let after_c5 = current_fen(&tree);
+        assert_eq!(tree.depth(), 2);
+        assert!(!tree.is_mainline());
+        assert_eq!(tree.node(&[0]).children().len(), 2);
+
+        // playing a move that was played before enters its node again
+        assert!(tree.back());
+        play(&mut tree, "e5");
+        assert_eq!(tree.node(&[0]).children().len(), 2);
+        assert_eq!(current_fen(&tree), after_e5);
+
+        assert!(tree.next_variation());
+        assert_eq!(current_fen(&tree), after_c5);
+        assert!(!tree.next_variation());
+        assert!(tree.previous_variation());
+        assert_eq!(current_fen(&tree), after_e5);
+        assert!(!tree.previous_variation());
+    }
+
+    #[test]
+    fn back_and_forward() {
+        let mut tree = GameTree::new(State::new(None));
+        assert!(!tree.back());
+        assert!(!tree.forward());
+
+        play(&mut tree, "e4");
+        play(&mut tree, "e5");
+        tree.back();
+        play(&mut tree, "c5");
+        play(&mut tree, "Nf3");
+        let after_nf3 = current_fen(&tree);
+
+        // going forward returns to the variation that was left ...
+        assert!(tree.back());
+        assert!(tree.back());
+        assert!(tree.forward());
+        assert!(tree.forward());
+        assert_eq!(current_fen(&tree), after_nf3);
+        assert!(!tree.forward());
+
+        // ... which is forgotten after switching variations, then it follows the first continuation
+        tree.back();
+        tree.back();
+        tree.back();
+        assert!(tree.forward());
+        assert!(tree.forward());
+        assert!(tree.previous_variation());
+        assert!(!tree.forward());
+        assert!(tree.next_variation());
+        assert!(tree.forward());
+        assert_eq!(current_fen(&tree), after_nf3);
+        assert_eq!(tree.line().len(), 4);
+    }
+
+    #[test]
+    fn promote_and_delete_variations() {
+        let mut tree = GameTree::new(State::new(None));
+        play(&mut tree, "e4");
+        play(&mut tree, "e5");
+        tree.back();
+        play(&mut tree, "c5");
+        play(&mut tree, "Nf3");
+        let variation: Vec<State> = tree.line().into_iter().cloned().collect();
+
+        assert!(tree.promote_to_mainline());
+        assert!(tree.is_mainline());
+        assert!(!tree.promote_to_mainline());
+        assert_eq!(
+            tree.mainline().into_iter().cloned().collect::<Vec<State>>(),
+            variation
+        );
+
+        // deleting c5 removes Nf3 as well
+        tree.back();
+        assert!(tree.delete_current());
+        assert_eq!(tree.depth(), 1);
+        assert_eq!(tree.current().children().len(), 1);
+        assert_eq!(tree.mainline().len(), 3);
+
+        tree.back();
+        assert!(!tree.delete_current());
+    }
+}
@@ -0,0 +1,131 @@
+use crate::library::game::{Field, Move, MoveError, PieceType, State};
+
+/*
+    The UCI protocol writes moves as their start and target field, followed by the piece a pawn is promoted to
+    (e.g. "e2e4", "e7e8q"). Castling is written as the king move: "e1g1" in standard chess, while in Chess960 the
+    king moves onto the rook it castles with (e.g. "b1a1"). This is exactly how castling is represented internally.
+*/
+impl State {
+    pub fn parse_uci(&self, uci: &str) -> Result<Move, MoveError> {
+        let chars: Vec<char> = uci.trim().chars().collect();
+        if chars.len() != 4 && chars.len() != 5 {
+            return Err(MoveError::InvalidNotation);
+        }
+
+        let (start_field, target_field) = match (
+            State::uci_field(chars[0], chars[1]),
+            State::uci_field(chars[2], chars[3]),
+        ) {
+            (Some(start_field), Some(target_field)) => (start_field, target_field),
+            _ => return Err(MoveError::InvalidNotation),
+        };
+
+        let promotion = match chars.get(4).map(|c| c.to_ascii_lowercase()) {
+            Some('q') => Some(PieceType::Queen),
+            Some('r') => Some(PieceType::Rook),
+            Some('b') => Some(PieceType::Bishop),
+            Some('n') => Some(PieceType::Knight),
+            Some(_) => return Err(MoveError::InvalidPromotion),
+            None => None,
+        };
+
+        let mut chess_move = Move::new(&start_field, &target_field, self);
+        chess_move.promotion = promotion;
+        match self.is_move_legal(&chess_move) {
+            Ok(_) => Ok(chess_move),
+            Err(e) => Err(e),
+        }
+    }
+
+    // whether the input has the shape of a UCI move (not whether the move is legal)
+    pub fn is_uci(input: &str) -> bool {
+        let chars: Vec<char> = input.trim().chars().collect();
+        (chars.len() == 4 || chars.len() == 5)
+            && State::uci_field(chars[0], chars[1]).is_some()
+            && State::uci_field(chars[2], chars[3]).is_some()
+    }
+
+    fn uci_field(file: char, rank: char) -> Option<Field> {
+        match (file, rank) {
+            ('a'..='h', '1'..='8') => Some(Field(
+                rank as usize - '1' as usize,
+                file as usize - 'a' as usize,
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl Move {
+    pub fn to_uci(&self) -> String {
+        let promotion = match self.promotion() {
+            Some(PieceType::Queen) => "q",
+            Some(PieceType::Rook) => "r",
+            Some(PieceType::Bishop) => "b",
+            Some(PieceType::Knight) => "n",
+            _ => "",
+        };
+        format!(
+            "{}{}{}{}{}",
+            (b'a' + self.start_field().1 as u8) as char,
+            self.start_field().0 + 1,
+            (b'a' + self.target_field().1 as u8) as char,
+            self.target_field().0 + 1,
+            promotion
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn uci_move(fen: &str, uci: &str) -> Result<String, MoveError> {
+        let state = State::load_game_from_fen(String::from(fen));
+        match state.parse_uci(uci) {
+            Ok(chess_move) => Ok(chess_move.to_uci()),
+            Err(e) => Err(e),
+        }
+    }
+
+    #[test]
+    fn parse_uci() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(uci_move(fen, "e2e4"), Ok(String::from("e2e4")));
+        assert_eq!(uci_move(fen, "g1f3\n"), Ok(String::from("g1f3")));
+        assert_eq!(uci_move(fen, "e2e5"), Err(MoveError::PieceCantReachTarget));
+        assert_eq!(uci_move(fen, "e7e5"), Err(MoveError::WrongColorSelected));
+        assert_eq!(uci_move(fen, "e2e9"), Err(MoveError::InvalidNotation));
+        assert_eq!(uci_move(fen, "e2"), Err(MoveError::InvalidNotation));
+        assert_eq!(uci_move(fen, "e2e4k"), Err(MoveError::InvalidPromotion));
+
+        let fen = "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(uci_move(fen, "a7a8n"), Ok(String::from("a7a8n")));
+        assert_eq!(uci_move(fen, "a7b8q"), Ok(String::from("a7b8q")));
+        assert_eq!(uci_move(fen, "a7a8"), Err(MoveError::PromotionRequired));
+    }
+
+    #[test]
+    fn parse_uci_castling() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(uci_move(fen, "e1g1"), Ok(String::from("e1g1")));
+        assert_eq!(uci_move(fen, "e1c1"), Ok(String::from("e1c1")));
+        assert!(uci_move(fen, "e1h1").is_err());
+        assert!(uci_move(fen, "e1a1").is_err());
+
+        // in Chess960 the king moves onto the rook
+        let fen = "r4k1r/8/8/8/8/8/8/RK5R w AHah - 0 1";
+        assert_eq!(uci_move(fen, "b1a1"), Ok(String::from("b1a1")));
+        assert_eq!(uci_move(fen, "b1h1"), Ok(String::from("b1h1")));
+    }
+
+    #[test]
+    fn is_uci() {
+        assert!(State::is_uci("e2e4"));
+        assert!(State::is_uci("a7a8q"));
+        assert!(!State::is_uci("2545"));
+        assert!(!State::is_uci("Nf3"));
+        assert!(!State::is_uci("O-O-O"));
+    }
+}
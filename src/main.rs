@@ -1,4 +1,4 @@
-use rust_chess::library::{cli, config::*, gui};
+use rust_chess::library::{cli, config::*, game::GameState, gui};
 use std::{env, error::Error, process};
 
 fn main() {
@@ -16,9 +16,17 @@ fn main() {
 }
 
 fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    // a game loaded from a PGN file continues after its last move
+    let game = match &config.pgn {
+        Some(pgn) => pgn.game_state(),
+        None => GameState::new(config.state()),
+    };
+
     match config.ui_type {
-        UiType::CLI => cli::run(config.fen),
-        UiType::GUI => unsafe { gui::run(config.fen) },
+        UiType::CLI => cli::run(game),
+        UiType::GUI => unsafe { gui::run(game) },
+        UiType::Perft(depth) => cli::run_perft(config.state(), depth),
+        UiType::Epd(records, answers) => cli::run_epd(&records, &answers),
     }
     Ok(())
 }